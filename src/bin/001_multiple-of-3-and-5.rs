@@ -11,6 +11,7 @@ trait SumOfMultiples {
     fn solve(&self, input: Input) -> i32;
 }
 
+#[allow(dead_code)]
 struct Brute;
 
 impl SumOfMultiples for Brute {
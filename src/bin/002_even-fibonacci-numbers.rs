@@ -19,11 +19,6 @@ struct Brute;
 
 impl EvenFibonacciNumbers for Brute {
     fn sum_even_fibonacci_numbers(&self, input: Input) -> u32 {
-        let mut items = Fibonacci::new()
-            .take_while(|&n| n <= input.upper_bound)
-            .filter(|&n| n % 2 == 0)
-            .collect::<Vec<_>>();
-        dbg!(items);
         Fibonacci::new()
             .take_while(|&n| n <= input.upper_bound)
             .filter(|&n| n % 2 == 0)
@@ -32,9 +27,7 @@ impl EvenFibonacciNumbers for Brute {
 }
 
 fn main() -> Result<(), Box<dyn StdError>> {
-    let input = Input {
-        upper_bound: 4_000_000,
-    };
+    let input = Input::new(4_000_000);
     let answer = Brute.sum_even_fibonacci_numbers(input);
     dbg!(answer);
     Ok(())
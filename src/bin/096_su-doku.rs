@@ -1,90 +1,127 @@
 use std::error::Error as StdError;
+use std::time::Instant;
 
 use itertools::Itertools;
+use project_euler::sudoku::{
+    BatchSolver, Board, BoardLoader, BoardParseError, Digit, DigitSet, DlxSolver, ItemPosition,
+    Solver, SolverObserver, Square, StepOutcome,
+};
 
-use project_euler::sudoku::{Board, BoardLoader, Solver, Square};
-
-fn load_boards() -> Vec<Board<Square>> {
+fn load_boards() -> Result<Vec<Board<Square>>, BoardParseError> {
     let s = include_str!("../../assets/p096_sudoku.txt");
-    s.lines()
-        .filter(|s| !s.starts_with("Grid"))
-        .chunks(9) // 9x9
-        .into_iter()
-        .map(BoardLoader::from_lines)
-        // .skip(6)
-        // .take(1)
-        .collect()
+    BoardLoader::load(s)
+}
+
+// 1つの盤面を解く過程を出力する
+struct Trace;
+
+impl SolverObserver for Trace {
+    fn on_filter_end(&mut self, filter: &'static str, changed: bool) {
+        if changed {
+            println!("{}", filter);
+        }
+    }
+
+    fn on_fix(&mut self, pos: ItemPosition, digit: Digit) {
+        println!("  fix {} = {}", pos, digit);
+    }
+
+    fn on_branch(&mut self, pos: ItemPosition, digits: DigitSet) {
+        println!("got stuck, guess {} from {}", pos, digits.iter().join(","));
+    }
+
+    fn on_backtrack(&mut self) {
+        println!("contradiction, backtrack");
+    }
+}
+
+fn trace(no: usize, board: Board<Square>) -> Result<(), Box<dyn StdError>> {
+    println!("[Board#{}]", no);
+    board.show();
+
+    let mut solver = Solver::new(board);
+    solver.add_observer(Trace);
+    let report = solver.solve();
+
+    println!("{:?} after {} updates", report.outcome, report.updates);
+    solver.board().show();
+    if !report.is_solved() {
+        solver.candidates().show();
+    }
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn StdError>> {
-    let boards = load_boards();
+    let mut boards = load_boards()?;
     let num_boards = boards.len();
     let mut solved = 0;
     let mut failure = 0;
 
-    let enable_debugging = false;
-    let debug_steps = false;
+    // 盤面の番号を指定すると、その盤面を解く過程を出力する
+    if let Some(arg) = std::env::args().nth(1) {
+        let no = arg.parse::<usize>()?;
+        if no == 0 || no > num_boards {
+            return Err(format!("board number must be 1..={}", num_boards).into());
+        }
+        return trace(no, boards.swap_remove(no - 1));
+    }
 
-    // let enable_debugging = true;
-    // let debug_steps = true;
+    let start = Instant::now();
+    let results = BatchSolver::new().solve(&boards);
+    let elapsed = start.elapsed();
 
     let mut sum = 0;
-    for (no, board) in boards.into_iter().enumerate().map(|(i, b)| (i + 1, b)) {
-        let board_original = board.clone();
-        let mut solver = Solver::new(board);
+    for (no, (board, result)) in boards.into_iter().zip(results).enumerate() {
+        let no = no + 1;
+        let result = result?;
+        let mut reference = DlxSolver::new(board);
+        reference.solve()?;
 
-        if enable_debugging {
-            solver.board().show();
-        }
-        let mut updated = 0;
-        while solver.update()? {
-            updated += 1;
-
-            if enable_debugging && debug_steps {
-                println!();
-                println!(
-                    "################################################################################"
-                );
-                println!();
-                println!("Board (update:{})", updated);
-                solver.board().show();
-                println!("Candidates (update:{})", updated);
-                solver.candidates().show();
+        let complete = result.is_solved();
+        let matches = result.board.items().eq(reference.board().items());
+        let status_label = match result.report.outcome {
+            StepOutcome::Solved if matches => {
+                solved += 1;
+                "Complete!"
+            }
+            StepOutcome::Solved => {
+                failure += 1;
+                "Mismatch"
+            }
+            StepOutcome::Unsolvable => {
+                failure += 1;
+                "Unsolvable"
+            }
+            _ => {
+                failure += 1;
+                "Stuck"
             }
-        }
-
-        let complete = solver.board().is_complete();
-        let status_label = if complete {
-            solved += 1;
-            "Complete!"
-        } else {
-            failure += 1;
-            "Failure"
         };
 
-        let stats = solver.statistics();
+        let stats = &result.report.statistics;
         println!(
-            "Board#{}: {} (with {} updates, {} got stuck, {} backtrack)",
-            no, status_label, updated, stats.get_stuck, stats.back_tracked
+            "Board#{}: {} (with {} updates, {} got stuck, {} backtrack, difficulty {:.1} by {}, score {:.0}, {:?})",
+            no,
+            status_label,
+            result.report.updates,
+            stats.get_stuck,
+            stats.back_tracked,
+            stats.difficulty(),
+            stats.hardest_filter().unwrap_or("-"),
+            stats.score(),
+            result.elapsed
         );
 
-        if enable_debugging && !complete {
-            println!();
-            println!(
-                "################################################################################"
-            );
-            println!("[Board#{}]: update{}", no, updated);
-            solver.board().show();
-            solver.candidates().show();
+        if !complete {
+            result.board.show();
         }
 
         if failure == 0 {
-            let three_digits = solver
-                .board()
+            let three_digits = result
+                .board
                 .items()
                 .take(3)
-                .fold(0u32, |acc, sq| acc * 10 + sq.digit().unwrap().get() as u32)
-                as u32;
+                .fold(0u32, |acc, sq| acc * 10 + sq.digit().unwrap().get() as u32);
 
             sum += three_digits;
         }
@@ -92,5 +129,6 @@ fn main() -> Result<(), Box<dyn StdError>> {
 
     println!("solved: {}/{} (failure:{})", solved, num_boards, failure);
     println!("sum: {}", sum);
+    println!("elapsed: {:?}", elapsed);
     Ok(())
 }
@@ -12,6 +12,12 @@ impl Fibonacci {
     }
 }
 
+impl Default for Fibonacci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Iterator for Fibonacci {
     type Item = u32;
 
@@ -37,6 +37,10 @@ impl Flags32 {
         self.0 &= !mask_for(pos);
     }
 
+    pub fn last_set(&self) -> Option<usize> {
+        (0..32).rev().find(|pos| self.get(*pos))
+    }
+
     pub fn iter(&self) -> Flags32Iter {
         Flags32Iter {
             flags: *self,
@@ -1,15 +1,39 @@
 mod action;
+mod batch;
 mod board;
 mod candidate;
 mod digit;
+mod dlx;
 mod event;
 mod filter;
+mod format;
+pub mod generator;
+mod observer;
 mod positions;
 mod printer;
 mod set;
 mod solver;
 mod square;
+mod step;
 
-pub use board::{Board, BoardLoader};
-pub use solver::Solver;
+pub use batch::{BatchError, BatchResult, BatchSolver};
+pub use board::{Board, BoardLoader, BoardParseError, Cage, ItemPosition, Unit, UnitKind};
+pub use candidate::Candidate;
+pub use digit::Digit;
+pub use dlx::DlxSolver;
+pub use event::Event;
+pub use filter::{
+    AlternatingInferenceChain, CageCombination, FilterCandidates, FilterInput, Fish, HiddenPair,
+    HiddenQuad, HiddenTriple, LockedCandidateClaiming, LockedCandidatePointing, NakedPair,
+    NakedQuad, NakedSingle, NakedTriple, NamedFilter, RuleOf45, SimpleColoring, SingleCandidate,
+    WWing, XChain, XYChain, XYWing, XYZWing, DEFAULT_DIFFICULTY,
+};
+pub use format::{BoardFormat, BoardWriter};
+pub use observer::SolverObserver;
+pub use set::DigitSet;
+pub use solver::{
+    FilterUsage, SolveReport, Solver, SolverBuilder, SolverError, SolverSnapshot, StateSnapshot,
+    Statistics, StatisticsSnapshot, StepOutcome,
+};
 pub use square::Square;
+pub use step::{Hint, House, Step, StepKind};
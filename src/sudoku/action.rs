@@ -1,6 +1,11 @@
+use std::fmt::Debug;
+
+use crate::sudoku::board::{ItemPosition, Scope};
 use crate::sudoku::candidate::Candidate;
 use crate::sudoku::digit::Digit;
-use crate::sudoku::positions::{BlockPositions, ColumnPositions, Positions, RowPositions};
+use crate::sudoku::positions::{
+    BlockPositions, CellPositions, ColumnPositions, Positions, RowPositions, UnitPositions,
+};
 use crate::sudoku::set::DigitSet;
 use crate::sudoku::Board;
 
@@ -9,6 +14,35 @@ pub enum ActionScope {
     Row(RowPositions),
     Column(ColumnPositions),
     Block(BlockPositions),
+    Unit(UnitPositions),
+    Cells(CellPositions),
+}
+
+impl ActionScope {
+    pub fn house(&self) -> Option<Scope> {
+        match *self {
+            ActionScope::Row(pos) => Some(Scope::Row(pos.row)),
+            ActionScope::Column(pos) => Some(Scope::Column(pos.column)),
+            ActionScope::Block(pos) => Some(Scope::BoardBlock(pos.block_at)),
+            ActionScope::Unit(pos) => Some(Scope::Unit(pos.unit)),
+            ActionScope::Cells(_) => None,
+        }
+    }
+
+    pub fn item_positions<T: Debug + Clone>(&self, board: &Board<T>) -> Vec<ItemPosition> {
+        let (house, positions) = match *self {
+            ActionScope::Row(pos) => (Scope::Row(pos.row), pos.columns),
+            ActionScope::Column(pos) => (Scope::Column(pos.column), pos.rows),
+            ActionScope::Block(pos) => (Scope::BoardBlock(pos.block_at), pos.indexes),
+            ActionScope::Unit(pos) => (Scope::Unit(pos.unit), pos.indexes),
+            ActionScope::Cells(cells) => {
+                return cells.iter().map(|i| board.item_position(i)).collect();
+            }
+        };
+        positions
+            .items_from_iter(board.scope_positions(house).into_iter())
+            .collect()
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -29,6 +63,14 @@ impl RetainAction {
         Self::new(digits, scope)
     }
 
+    pub fn digits(&self) -> DigitSet {
+        self.digits
+    }
+
+    pub fn scope(&self) -> ActionScope {
+        self.scope
+    }
+
     pub fn retain(self, candidates: &mut Board<Candidate>) -> bool {
         match self.scope {
             ActionScope::Row(pos) => {
@@ -40,6 +82,12 @@ impl RetainAction {
             ActionScope::Block(pos) => {
                 self.retain_iter(pos.indexes, candidates.block_at_mut(pos.block_at))
             }
+            ActionScope::Unit(pos) => {
+                self.retain_iter(pos.indexes, candidates.unit_items_mut(pos.unit))
+            }
+            ActionScope::Cells(cells) => cells
+                .items_from_iter(candidates.items_mut())
+                .fold(false, |updated, c| self.retain_candidate(c) | updated),
         }
     }
 
@@ -47,9 +95,14 @@ impl RetainAction {
     where
         I: Iterator<Item = &'a mut Candidate>,
     {
-        positions.items_from_iter(iter).fold(false, |updated, c| {
-            c.remove_iter(Digit::all_digits_iter().filter(|d| !self.digits.contains(*d))) || updated
-        })
+        positions
+            .items_from_iter(iter)
+            .fold(false, |updated, c| self.retain_candidate(c) | updated)
+    }
+
+    fn retain_candidate(self, c: &mut Candidate) -> bool {
+        let digits = c.digits();
+        c.remove_iter(digits.iter().filter(|d| !self.digits.contains(*d)))
     }
 }
 
@@ -64,6 +117,14 @@ impl RemoveAction {
         RemoveAction { digit, scope }
     }
 
+    pub fn digit(&self) -> Digit {
+        self.digit
+    }
+
+    pub fn scope(&self) -> ActionScope {
+        self.scope
+    }
+
     pub fn remove(self, candidates: &mut Board<Candidate>) -> bool {
         match self.scope {
             ActionScope::Row(pos) => {
@@ -75,6 +136,12 @@ impl RemoveAction {
             ActionScope::Block(pos) => {
                 self.remove_iter(pos.indexes, candidates.block_at_mut(pos.block_at))
             }
+            ActionScope::Unit(pos) => {
+                self.remove_iter(pos.indexes, candidates.unit_items_mut(pos.unit))
+            }
+            ActionScope::Cells(cells) => cells
+                .items_from_iter(candidates.items_mut())
+                .fold(false, |updated, c| c.remove(self.digit) | updated),
         }
     }
 
@@ -84,6 +151,6 @@ impl RemoveAction {
     {
         positions
             .items_from_iter(iter)
-            .fold(false, |updated, c| c.remove(self.digit) || updated)
+            .fold(false, |updated, c| c.remove(self.digit) | updated)
     }
 }
@@ -0,0 +1,216 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::sudoku::board::Board;
+use crate::sudoku::solver::{SolveReport, SolverBuilder};
+use crate::sudoku::Square;
+
+// 盤面ごとの結果、solution は解けた場合だけ入る
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub solution: Option<Board<Square>>,
+    pub board: Board<Square>,
+    pub report: SolveReport,
+    pub elapsed: Duration,
+}
+
+impl BatchResult {
+    pub fn is_solved(&self) -> bool {
+        self.solution.is_some()
+    }
+}
+
+// 1つの盤面で失敗しても、他の盤面の結果は返す
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("solver panicked. board:{index}, message:{message}")]
+    Panicked { index: usize, message: String },
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+// 複数の盤面をスレッドに分けて解く、結果は入力と同じ順に返す
+pub struct BatchSolver {
+    builder: Box<dyn Fn() -> SolverBuilder + Sync>,
+    threads: usize,
+}
+
+impl Default for BatchSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchSolver {
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        BatchSolver {
+            builder: Box::new(SolverBuilder::new),
+            threads,
+        }
+    }
+
+    // スレッドごとに Solver を組み立てるので、SolverBuilder を返す関数で指定する
+    pub fn builder<F: Fn() -> SolverBuilder + Sync + 'static>(mut self, builder: F) -> Self {
+        self.builder = Box::new(builder);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn solve(&self, boards: &[Board<Square>]) -> Vec<Result<BatchResult, BatchError>> {
+        // 空いたスレッドから次の盤面を取りに行く
+        let next = AtomicUsize::new(0);
+        let worker = || {
+            let mut results = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match boards.get(i) {
+                    Some(board) => results.push((i, self.solve_one(i, board.clone()))),
+                    None => break results,
+                }
+            }
+        };
+
+        let mut results = thread::scope(|scope| {
+            let handles = (0..self.threads.min(boards.len()))
+                .map(|_| scope.spawn(worker))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    fn solve_one(&self, index: usize, board: Board<Square>) -> Result<BatchResult, BatchError> {
+        // 盤面ごとにパニックを捕まえて、その盤面のエラーとして返す
+        let start = Instant::now();
+        let (board, report) = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut solver = (self.builder)().build(board);
+            let report = solver.solve();
+            let board = solver.board().clone();
+            (board, report)
+        }))
+        .map_err(|payload| BatchError::Panicked {
+            index,
+            message: panic_message(payload),
+        })?;
+
+        Ok(BatchResult {
+            solution: Some(board.clone()).filter(|_| report.is_solved()),
+            board,
+            report,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::sudoku::{
+        BatchError, BatchSolver, BoardLoader, Digit, FilterCandidates, FilterInput, ItemPosition,
+        NakedSingle, NamedFilter, Solver, SolverBuilder,
+    };
+
+    // 左上が 2 の盤面でパニックする
+    struct Explode;
+
+    impl NamedFilter for Explode {
+        fn name(&self) -> &'static str {
+            "Explode"
+        }
+    }
+
+    impl FilterCandidates for Explode {
+        fn filter_candidates(&self, input: FilterInput) {
+            let top_left = input
+                .board()
+                .item_at(ItemPosition { row: 0, col: 0 })
+                .digit();
+            if top_left == Some(Digit::from(2)) {
+                panic!("explode");
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let s = include_str!("../../assets/p096_sudoku.txt");
+        let boards = BoardLoader::load(s).unwrap();
+
+        let results = BatchSolver::new()
+            .threads(4)
+            .solve(&boards)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect_vec();
+        assert_eq!(boards.len(), results.len());
+        assert!(results.iter().all(|result| result.is_solved()));
+
+        // 入力と同じ順に並び、逐次に解いた結果と一致する
+        for (board, result) in boards.iter().zip(results.iter()).take(5) {
+            let mut solver = Solver::new(board.clone());
+            solver.solve();
+            let solution = result.solution.as_ref().unwrap();
+            assert!(solution.items().eq(solver.board().items()));
+            assert_eq!(
+                solver.statistics().filters.len(),
+                result.report.statistics.filters.len()
+            );
+        }
+
+        // フィルタを絞ると解けない盤面は solution が入らない
+        let results = BatchSolver::new()
+            .builder(|| {
+                SolverBuilder::new()
+                    .only(&["NakedSingle"])
+                    .backtracking(false)
+            })
+            .threads(2)
+            .solve(&boards[..4])
+            .into_iter()
+            .map(Result::unwrap)
+            .collect_vec();
+        assert_eq!(4, results.len());
+        assert!(results.iter().any(|result| !result.is_solved()));
+        assert!(results
+            .iter()
+            .filter(|result| !result.is_solved())
+            .all(|result| !result.board.is_complete()));
+
+        // パニックした盤面だけがエラーになり、残りの盤面は解ける
+        let results = BatchSolver::new()
+            .builder(|| SolverBuilder::empty().filter(Explode).filter(NakedSingle))
+            .threads(2)
+            .solve(&boards[..2]);
+        assert!(results[0].as_ref().unwrap().is_solved());
+        match &results[1] {
+            Err(BatchError::Panicked { index, message }) => {
+                assert_eq!(1, *index);
+                assert_eq!("explode", message);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
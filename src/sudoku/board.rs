@@ -1,10 +1,12 @@
 use std::fmt::{self, Debug};
 
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 use thiserror::Error;
 
 use crate::sudoku::candidate::Candidate;
-use crate::sudoku::digit::Digit;
+use crate::sudoku::digit::{self, Digit};
+use crate::sudoku::dlx::ExactCover;
+use crate::sudoku::positions::CellPositions;
 use crate::sudoku::printer::{BoardPrinter, Printer};
 use crate::sudoku::square::Square;
 use std::convert::TryFrom;
@@ -17,11 +19,81 @@ fn enumerate_table_positions(
     (0..num_rows).flat_map(move |row| (0..num_cols).map(move |col| (row, col)))
 }
 
+// 数字を1文字で表せる 25x25 までの盤面を扱う
+pub(crate) const MAX_BOARD_WIDTH: usize = digit::MAX as usize;
+
+// 盤面の大きさからブロックの形(高さ, 幅)を決める、6x6 なら 2x3、12x12 なら 3x4
+pub(crate) fn block_shape(size: usize) -> Option<(usize, usize)> {
+    if size > MAX_BOARD_WIDTH {
+        return None;
+    }
+    let height = (2..=size)
+        .take_while(|h| h * h <= size)
+        .filter(|h| size % h == 0)
+        .last()?;
+    Some((height, size / height))
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Scope {
     Row(usize),
     Column(usize),
     BoardBlock(BlockPosition),
+    Unit(usize),
+    Cage(usize),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnitKind {
+    Diagonal,
+    AntiDiagonal,
+    Window,
+    Custom,
+}
+
+// 行・列・ブロック以外に数字の重複を許さないセルの集まり
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unit {
+    kind: UnitKind,
+    cells: Vec<usize>,
+}
+
+impl Unit {
+    pub fn kind(&self) -> UnitKind {
+        self.kind
+    }
+
+    pub fn cells(&self) -> &[usize] {
+        &self.cells
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.cells.binary_search(&index).is_ok()
+    }
+}
+
+// キラーサドクのケージ、数字は重複せず合計が sum になる
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cage {
+    sum: u32,
+    cells: Vec<usize>,
+}
+
+impl Cage {
+    pub fn sum(&self) -> u32 {
+        self.sum
+    }
+
+    pub fn cells(&self) -> &[usize] {
+        &self.cells
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.cells.binary_search(&index).is_ok()
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -42,30 +114,274 @@ pub struct BlockPosition {
     pub col: usize,
 }
 
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum BoardParseError {
+    #[error("unknown board format")]
+    UnknownFormat,
+    #[error("wrong board size. {height} lines cannot be divided into blocks")]
+    WrongSize { height: usize },
+    #[error("wrong number of cells. {found} cells cannot be divided into blocks")]
+    WrongCellCount { found: usize },
+    #[error("wrong line length at line {line}. expected {expected} chars, found {found} chars")]
+    WrongLineLength {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("invalid character '{found}' at line {line}, column {column}")]
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    #[error(
+        "'0' at line {line}, column {column} is not allowed on boards wider than 9. \
+         use 1-9 and A-P for digits and '.' for blanks"
+    )]
+    HexDigit { line: usize, column: usize },
+    #[error("invalid cage sum at line {line}")]
+    InvalidCageSum { line: usize },
+    #[error("cage '{label}' has no sum")]
+    MissingCageSum { label: char },
+    #[error("cage '{label}' has {found} cells, more than a house")]
+    WrongCageSize { label: char, found: usize },
+    #[error("wrong number of regions. expected {expected} regions, found {found} regions")]
+    WrongRegionCount { expected: usize, found: usize },
+    #[error("region '{label}' has {found} cells, expected {expected} cells")]
+    WrongRegionSize {
+        label: char,
+        expected: usize,
+        found: usize,
+    },
+}
+
 #[derive(Debug, Error)]
 pub enum BoardError {
     #[error("duplication error. digit:{0:?}, scope:{1:?}")]
     Duplication(Scope, Vec<Digit>),
+    #[error("cage sum error. cage:{0}, sum:{1}, expected:{2}")]
+    CageSum(usize, u32, u32),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "BoardData<T>",
+        bound(deserialize = "T: serde::Deserialize<'de>")
+    )
+)]
 pub struct Board<T: Debug + Clone> {
     items: Vec<T>,
-    block_size: usize,
-    num_blocks: usize,
+    block_height: usize,
+    block_width: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    units: Vec<Unit>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    cages: Vec<Cage>,
+    // ジグソーの場合はセルごとのブロック番号、None なら長方形のブロック
+    #[cfg_attr(feature = "serde", serde(default))]
+    regions: Option<Vec<usize>>,
+}
+
+// 復元時に盤面の形を検証するための、Board と同じ形のデータ
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BoardData<T> {
+    items: Vec<T>,
+    block_height: usize,
+    block_width: usize,
+    #[serde(default)]
+    units: Vec<Unit>,
+    #[serde(default)]
+    cages: Vec<Cage>,
+    #[serde(default)]
+    regions: Option<Vec<usize>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Debug + Clone> TryFrom<BoardData<T>> for Board<T> {
+    type Error = anyhow::Error;
+
+    fn try_from(data: BoardData<T>) -> Result<Self, Self::Error> {
+        let width = data.block_height * data.block_width;
+        let len = data.items.len();
+        if width == 0 || width > MAX_BOARD_WIDTH || len != width * width {
+            return Err(anyhow::anyhow!(
+                "invalid board shape. block:{}x{}, items:{}",
+                data.block_height,
+                data.block_width,
+                len
+            ));
+        }
+
+        // ユニット・ケージのセルは盤面内で、昇順に重複なく並んでいる
+        let in_board = |cells: &[usize]| {
+            cells.iter().all(|i| *i < len) && cells.windows(2).all(|w| w[0] < w[1])
+        };
+        if let Some(unit) = data
+            .units
+            .iter()
+            .find(|unit| unit.cells.len() != width || !in_board(&unit.cells))
+        {
+            return Err(anyhow::anyhow!("invalid unit. cells:{:?}", unit.cells));
+        }
+        if let Some(cage) = data.cages.iter().find(|cage| {
+            cage.cells.is_empty() || cage.cells.len() > width || !in_board(&cage.cells)
+        }) {
+            return Err(anyhow::anyhow!("invalid cage. cells:{:?}", cage.cells));
+        }
+        if let Some(regions) = &data.regions {
+            let valid = regions.len() == len
+                && (0..width).all(|block| regions.iter().filter(|r| **r == block).count() == width);
+            if !valid {
+                return Err(anyhow::anyhow!("invalid regions. regions:{:?}", regions));
+            }
+        }
+
+        Ok(Board {
+            items: data.items,
+            block_height: data.block_height,
+            block_width: data.block_width,
+            units: data.units,
+            cages: data.cages,
+            regions: data.regions,
+        })
+    }
 }
 
 impl<T: Debug + Clone> Board<T> {
-    pub fn new(items: Vec<T>, block_size: usize, num_blocks: usize) -> Self {
+    pub fn new(items: Vec<T>, block_height: usize, block_width: usize) -> Self {
         Board {
             items,
-            block_size,
-            num_blocks,
+            block_height,
+            block_width,
+            units: Vec::new(),
+            cages: Vec::new(),
+            regions: None,
         }
     }
 
+    // 盤面の形と追加のユニットを引き継いで、別の要素の盤面を作る
+    pub fn with_items<U: Debug + Clone>(&self, items: Vec<U>) -> Board<U> {
+        assert_eq!(self.items.len(), items.len());
+        Board {
+            items,
+            block_height: self.block_height,
+            block_width: self.block_width,
+            units: self.units.clone(),
+            cages: self.cages.clone(),
+            regions: self.regions.clone(),
+        }
+    }
+
+    pub fn add_unit(&mut self, kind: UnitKind, cells: impl IntoIterator<Item = ItemPosition>) {
+        let mut cells = cells
+            .into_iter()
+            .map(|pos| self.item_index(pos))
+            .collect_vec();
+        cells.sort_unstable();
+        cells.dedup();
+        assert_eq!(self.width(), cells.len());
+
+        self.units.push(Unit { kind, cells });
+    }
+
+    // 対角線を X-Sudoku のユニットとして追加する
+    pub fn add_diagonals(&mut self) {
+        let size = self.width();
+        self.add_unit(
+            UnitKind::Diagonal,
+            (0..size).map(|i| ItemPosition { row: i, col: i }),
+        );
+        self.add_unit(
+            UnitKind::AntiDiagonal,
+            (0..size).map(|i| ItemPosition {
+                row: i,
+                col: size - 1 - i,
+            }),
+        );
+    }
+
+    // ブロックの間に1マスずつ隙間を空けて並べた Hyper-Sudoku の窓を追加する
+    pub fn add_windows(&mut self) {
+        let (height, width, size) = (self.block_height, self.block_width, self.width());
+        let rows = (1..).step_by(height + 1).take_while(|r| r + height < size);
+        let cols = (1..).step_by(width + 1).take_while(|c| c + width < size);
+        for (row, col) in rows.cartesian_product(cols.collect_vec()) {
+            let cells = enumerate_table_positions(height, width).map(|(r, c)| ItemPosition {
+                row: row + r,
+                col: col + c,
+            });
+            self.add_unit(UnitKind::Window, cells);
+        }
+    }
+
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
+    pub fn each_units(&self) -> Range<usize> {
+        0..self.units.len()
+    }
+
+    pub fn unit_items(&self, unit: usize) -> impl Iterator<Item = &T> {
+        self.units[unit].cells.iter().map(move |i| &self.items[*i])
+    }
+
+    pub fn add_cage(&mut self, sum: u32, cells: impl IntoIterator<Item = ItemPosition>) {
+        let mut cells = cells
+            .into_iter()
+            .map(|pos| self.item_index(pos))
+            .collect_vec();
+        cells.sort_unstable();
+        cells.dedup();
+        assert!(!cells.is_empty() && cells.len() <= self.width());
+
+        self.cages.push(Cage { sum, cells });
+    }
+
+    pub fn cages(&self) -> &[Cage] {
+        &self.cages
+    }
+
+    pub fn each_cages(&self) -> Range<usize> {
+        0..self.cages.len()
+    }
+
+    pub fn cage_items(&self, cage: usize) -> impl Iterator<Item = &T> {
+        self.cages[cage].cells.iter().map(move |i| &self.items[*i])
+    }
+
+    pub fn cage_of(&self, pos: ItemPosition) -> Option<usize> {
+        let index = self.item_index(pos);
+        self.cages.iter().position(|cage| cage.contains(index))
+    }
+
+    // セルごとのブロック番号で、長方形のブロックを任意の形の領域に置き換える
+    pub fn set_regions(&mut self, regions: Vec<usize>) {
+        assert_eq!(self.items.len(), regions.len());
+        for block in 0..self.width() {
+            assert_eq!(
+                self.width(),
+                regions.iter().filter(|r| **r == block).count()
+            );
+        }
+
+        self.regions = Some(regions);
+    }
+
+    pub fn regions(&self) -> Option<&[usize]> {
+        self.regions.as_deref()
+    }
+
+    pub fn is_jigsaw(&self) -> bool {
+        self.regions.is_some()
+    }
+
     pub fn block_positions(&self) -> impl Iterator<Item = BlockPosition> {
-        enumerate_table_positions(self.num_blocks(), self.num_blocks()).map(
+        enumerate_table_positions(self.num_block_rows(), self.num_block_columns()).map(
             |(block_row, block_col)| BlockPosition {
                 row: block_row,
                 col: block_col,
@@ -73,40 +389,159 @@ impl<T: Debug + Clone> Board<T> {
         )
     }
 
-    pub fn block_size(&self) -> usize {
-        self.block_size
+    pub fn block_height(&self) -> usize {
+        self.block_height
+    }
+
+    pub fn block_width(&self) -> usize {
+        self.block_width
     }
 
     pub fn each_block_columns(&self) -> Range<usize> {
-        0..self.block_size
+        0..self.block_width
     }
 
     pub fn each_block_rows(&self) -> Range<usize> {
-        0..self.block_size
+        0..self.block_height
     }
 
-    pub fn num_blocks(&self) -> usize {
-        self.num_blocks
+    pub fn num_block_rows(&self) -> usize {
+        self.height() / self.block_height
     }
 
-    pub fn block_item_indexes(&self) -> Range<usize> {
-        0..(self.block_size * self.num_blocks)
+    pub fn num_block_columns(&self) -> usize {
+        self.width() / self.block_width
     }
 
-    pub fn block_at(&self, pos: BlockPosition) -> BoardBlock<impl Iterator<Item = &T>> {
-        assert!(pos.row < self.num_blocks);
-        assert!(pos.col < self.num_blocks);
+    pub fn block_index(&self, pos: BlockPosition) -> usize {
+        pos.row * self.num_block_columns() + pos.col
+    }
 
-        let row = pos.row * self.num_blocks;
-        let col = pos.col * self.num_blocks;
+    fn block_position(&self, index: usize) -> BlockPosition {
+        BlockPosition {
+            row: index / self.num_block_columns(),
+            col: index % self.num_block_columns(),
+        }
+    }
 
-        let iter = self.each_block_rows().flat_map(move |row_offset| {
-            let index = self.index_of(row + row_offset, col);
-            self.items.iter().skip(index).take(self.block_size)
-        });
+    pub fn block_item_indexes(&self) -> Range<usize> {
+        0..(self.block_height * self.block_width)
+    }
+
+    pub fn block_at(&self, pos: BlockPosition) -> BoardBlock<impl Iterator<Item = &T>> {
+        assert!(pos.row < self.num_block_rows());
+        assert!(pos.col < self.num_block_columns());
+
+        let iter = match &self.regions {
+            Some(regions) => {
+                let block = self.block_index(pos);
+                let iter = self
+                    .items
+                    .iter()
+                    .zip(regions)
+                    .filter(move |(_, r)| **r == block)
+                    .map(|(item, _)| item);
+                Either::Right(iter)
+            }
+            None => {
+                let row = pos.row * self.block_height;
+                let col = pos.col * self.block_width;
+                let iter = self.each_block_rows().flat_map(move |row_offset| {
+                    let index = self.index_of(row + row_offset, col);
+                    self.items.iter().skip(index).take(self.block_width)
+                });
+                Either::Left(iter)
+            }
+        };
         BoardBlock {
             iter,
-            block_size: self.block_size,
+            block_height: self.block_height,
+            block_width: self.block_width,
+        }
+    }
+
+    pub fn block_position_of(&self, pos: ItemPosition) -> BlockPosition {
+        match &self.regions {
+            Some(regions) => self.block_position(regions[self.item_index(pos)]),
+            None => BlockPosition {
+                row: pos.row / self.block_height,
+                col: pos.col / self.block_width,
+            },
+        }
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = Scope> {
+        let rows = self.each_rows().map(Scope::Row);
+        let columns = self.each_columns().map(Scope::Column);
+        let blocks = self.block_positions().map(Scope::BoardBlock);
+        let units = self.each_units().map(Scope::Unit);
+        rows.chain(columns).chain(blocks).chain(units)
+    }
+
+    pub fn scope_positions(&self, scope: Scope) -> Vec<ItemPosition> {
+        match scope {
+            Scope::Row(row) => self
+                .each_columns()
+                .map(|col| ItemPosition { row, col })
+                .collect(),
+            Scope::Column(col) => self
+                .each_rows()
+                .map(|row| ItemPosition { row, col })
+                .collect(),
+            Scope::BoardBlock(pos) if self.is_jigsaw() => self
+                .item_positions()
+                .filter(|p| self.block_position_of(*p) == pos)
+                .collect(),
+            Scope::BoardBlock(pos) => {
+                enumerate_table_positions(self.block_height, self.block_width)
+                    .map(|(row, col)| ItemPosition {
+                        row: pos.row * self.block_height + row,
+                        col: pos.col * self.block_width + col,
+                    })
+                    .collect()
+            }
+            Scope::Unit(unit) => self.units[unit]
+                .cells
+                .iter()
+                .map(|i| self.item_position(*i))
+                .collect(),
+            Scope::Cage(cage) => self.cages[cage]
+                .cells
+                .iter()
+                .map(|i| self.item_position(*i))
+                .collect(),
+        }
+    }
+
+    pub fn peer_positions(&self, pos: ItemPosition) -> CellPositions {
+        // 同じ行・列・ブロック・ユニット・ケージに属するセル(自身を除く)
+        let block_pos = self.block_position_of(pos);
+        let mut peers = self
+            .item_positions()
+            .filter(|p| {
+                p.row == pos.row || p.col == pos.col || self.block_position_of(*p) == block_pos
+            })
+            .map(|p| self.item_index(p))
+            .collect::<CellPositions>();
+        let index = self.item_index(pos);
+        for unit in self.units.iter().filter(|u| u.contains(index)) {
+            peers = peers.or(&unit.cells.iter().copied().collect());
+        }
+        for cage in self.cages.iter().filter(|c| c.contains(index)) {
+            peers = peers.or(&cage.cells.iter().copied().collect());
+        }
+        peers.unset(index);
+        peers
+    }
+
+    pub fn item_index(&self, pos: ItemPosition) -> usize {
+        self.index_of(pos.row, pos.col)
+    }
+
+    pub fn item_position(&self, index: usize) -> ItemPosition {
+        ItemPosition {
+            row: index / self.width(),
+            col: index % self.width(),
         }
     }
 
@@ -130,7 +565,7 @@ impl<T: Debug + Clone> Board<T> {
     }
 
     pub fn width(&self) -> usize {
-        self.block_size * self.num_blocks
+        self.block_height * self.block_width
     }
 
     pub fn each_columns(&self) -> Range<usize> {
@@ -143,7 +578,7 @@ impl<T: Debug + Clone> Board<T> {
     }
 
     pub fn height(&self) -> usize {
-        self.block_size * self.num_blocks
+        self.block_height * self.block_width
     }
 
     pub fn each_rows(&self) -> Range<usize> {
@@ -163,6 +598,9 @@ impl<T: Debug + Clone> Board<T> {
 impl Board<Square> {
     pub fn fix_digit_at(&mut self, pos: ItemPosition, digit: Digit) -> Result<(), BoardError> {
         DuplicationValidator::new(self).validate(pos, Some(digit))?;
+        if let Some(cage) = self.cage_of(pos) {
+            CageValidator::new(self).validate(cage, Some(digit))?;
+        }
 
         let index = self.index_of(pos.row, pos.col);
         self.items[index].fix_digit(digit);
@@ -178,6 +616,24 @@ impl Board<Square> {
         self.items.iter().all(|sq| sq.is_fixed())
     }
 
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        // limit 個見つかった時点で探索を打ち切る
+        let mut count = 0;
+        ExactCover::new(self).search(|_| {
+            count += 1;
+            count < limit
+        });
+        count
+    }
+
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
     pub fn validate(&self) -> Result<(), BoardError> {
         let mut validator = DuplicationValidator::new(self);
 
@@ -193,11 +649,41 @@ impl Board<Square> {
             validator.validate_with_scope(Scope::BoardBlock(block_pos), None)?;
         }
 
+        for unit in self.each_units() {
+            validator.validate_with_scope(Scope::Unit(unit), None)?;
+        }
+
+        for cage in self.each_cages() {
+            validator.validate_with_scope(Scope::Cage(cage), None)?;
+            CageValidator::new(self).validate(cage, None)?;
+        }
+
         Ok(())
     }
 }
 
 impl Board<Candidate> {
+    pub fn squares(&self) -> Board<Square> {
+        // 候補が1つだけのセルは確定したものとみなす
+        let items = self
+            .items
+            .iter()
+            .map(|c| {
+                let digit = if c.is_fixed() {
+                    c.possible_digits().next()
+                } else {
+                    None
+                };
+                Square::new(digit, c.row(), c.column())
+            })
+            .collect();
+        self.with_items(items)
+    }
+
+    pub fn items_mut(&mut self) -> impl Iterator<Item = &mut Candidate> {
+        self.items.iter_mut()
+    }
+
     pub fn take_fixed_digit_at(&mut self, pos: ItemPosition) -> Option<Digit> {
         let index = self.index_of(pos.row, pos.col);
         self.items[index].take_fixed_digit()
@@ -215,41 +701,59 @@ impl Board<Candidate> {
         self.items.iter_mut().skip(index).step_by(width)
     }
 
+    pub fn unit_items_mut(&mut self, unit: usize) -> impl Iterator<Item = &mut Candidate> {
+        let unit = &self.units[unit];
+        self.items
+            .iter_mut()
+            .enumerate()
+            .filter(move |(i, _)| unit.contains(*i))
+            .map(|(_, c)| c)
+    }
+
     pub fn block_at_mut(
         &mut self,
         pos: BlockPosition,
     ) -> BoardBlock<impl Iterator<Item = &mut Candidate>> {
-        assert!(pos.row < self.num_blocks);
-        assert!(pos.col < self.num_blocks);
-
-        let row = pos.row * self.num_blocks;
-        let col = pos.col * self.num_blocks;
+        assert!(pos.row < self.num_block_rows());
+        assert!(pos.col < self.num_block_columns());
+
+        if let Some(regions) = &self.regions {
+            let block = self.block_index(pos);
+            let iter = self
+                .items
+                .iter_mut()
+                .zip(regions)
+                .filter(move |(_, r)| **r == block)
+                .map(|(square, _)| square);
+            return BoardBlock {
+                iter: Either::Right(iter),
+                block_height: self.block_height,
+                block_width: self.block_width,
+            };
+        }
 
-        let left_top_index = self.index_of(row, col);
+        let rows = (pos.row * self.block_height)..((pos.row + 1) * self.block_height);
+        let cols = (pos.col * self.block_width)..((pos.col + 1) * self.block_width);
+        let left_top_index = self.index_of(rows.start, cols.start);
+        let num_items = (self.block_height - 1) * self.width() + self.block_width;
         let width = self.width();
-        let height = self.height();
-        let block_size = self.block_size;
 
         let iter = self
             .items
             .iter_mut()
             .enumerate()
             .skip(left_top_index)
-            // TODO: takeで終了条件を追加すること、現状はブロック範囲外を抜けてもループしてる
+            .take(num_items)
             .filter(move |(index, _)| {
-                let r = index / height;
-                let c = index % width;
-
-                let row_matches = r >= row && r < row + block_size;
-                let col_matches = c >= col && c < col + block_size;
-
-                row_matches && col_matches
+                rows.contains(&(index / width)) && cols.contains(&(index % width))
             })
             .map(|(_, square)| square);
+        let iter = Either::Left(iter);
 
         BoardBlock {
             iter,
-            block_size: self.block_size,
+            block_height: self.block_height,
+            block_width: self.block_width,
         }
     }
 
@@ -260,20 +764,21 @@ impl Board<Candidate> {
 
 pub struct BoardBlock<I> {
     iter: I,
-    block_size: usize,
+    block_height: usize,
+    block_width: usize,
 }
 
 impl<I: Iterator> BoardBlock<I> {
     pub fn row_items(self, block_row: usize) -> impl Iterator<Item = I::Item> {
-        let offset = block_row * self.block_size;
-        self.iter.skip(offset).take(self.block_size)
+        let offset = block_row * self.block_width;
+        self.iter.skip(offset).take(self.block_width)
     }
 
     pub fn column_items(self, block_col: usize) -> impl Iterator<Item = I::Item> {
         self.iter
             .skip(block_col)
-            .step_by(self.block_size)
-            .take(self.block_size)
+            .step_by(self.block_width)
+            .take(self.block_height)
     }
 }
 
@@ -287,44 +792,91 @@ impl<I: Iterator> Iterator for BoardBlock<I> {
 
 pub struct BoardLoader {}
 
+// 入力上の位置(1始まり)を保持した1行分の文字
+pub(crate) struct SourceLine {
+    pub line: usize,
+    pub chars: Vec<(usize, char)>,
+}
+
+impl SourceLine {
+    pub fn new(line: usize, s: &str) -> Self {
+        SourceLine {
+            line,
+            chars: s.chars().enumerate().map(|(i, c)| (i + 1, c)).collect(),
+        }
+    }
+}
+
+// 10 以上の数字は 1-9 に続けて A-P で表す (16x16 なら 1-9A-G)。
+// 0-F の16進表記は扱わず、幅が 10 以上の盤面では '0' を空白とも数字ともみなさない
 impl BoardLoader {
-    pub fn from_lines<'a>(lines: impl Iterator<Item = &'a str>) -> Board<Square> {
-        let lines = lines.collect_vec();
+    pub(crate) fn is_blank(c: char) -> bool {
+        matches!(c, '.' | '0' | '_' | '*')
+    }
+
+    pub fn from_lines<'a>(
+        lines: impl Iterator<Item = &'a str>,
+    ) -> Result<Board<Square>, BoardParseError> {
+        let lines = lines
+            .enumerate()
+            .map(|(i, s)| SourceLine::new(i + 1, s))
+            .collect_vec();
+        Self::from_source_lines(lines)
+    }
+
+    pub(crate) fn from_source_lines(
+        lines: Vec<SourceLine>,
+    ) -> Result<Board<Square>, BoardParseError> {
         let height = lines.len();
-        let width = height;
+        let (block_height, block_width) =
+            block_shape(height).ok_or(BoardParseError::WrongSize { height })?;
+        Self::from_source_lines_with_shape(lines, block_height, block_width)
+    }
 
-        let block_size = (height as f64).sqrt() as usize;
-        if height % block_size != 0 {
-            panic!(
-                "wrong input. width={}, height={}, block_size={}",
-                width, height, block_size
-            );
+    pub(crate) fn from_source_lines_with_shape(
+        lines: Vec<SourceLine>,
+        block_height: usize,
+        block_width: usize,
+    ) -> Result<Board<Square>, BoardParseError> {
+        let width = block_height * block_width;
+        let height = lines.len();
+        if height != width {
+            return Err(BoardParseError::WrongSize { height });
         }
-        let items = lines
-            .into_iter()
-            .enumerate()
-            .flat_map(|(row, s)| {
-                if s.len() != width {
-                    panic!(
-                        "Wrong line, must contains {} chars, found {} chars on '{}'",
-                        width,
-                        s.len(),
-                        s
-                    );
-                }
-                s.chars().enumerate().map(move |(col, c)| {
-                    let d = Digit::try_from(c).ok();
-                    Square::new(d, row, col)
-                })
-            })
-            .collect::<Vec<_>>();
 
-        if items.len() != width * height {
-            panic!("Wrong board size");
+        let mut items = Vec::with_capacity(width * height);
+        for (row, source) in lines.into_iter().enumerate() {
+            if source.chars.len() != width {
+                return Err(BoardParseError::WrongLineLength {
+                    line: source.line,
+                    expected: width,
+                    found: source.chars.len(),
+                });
+            }
+
+            let line = source.line;
+            for (col, (column, c)) in source.chars.into_iter().enumerate() {
+                let d = if c == '0' && width > 9 {
+                    return Err(BoardParseError::HexDigit { line, column });
+                } else if Self::is_blank(c) {
+                    None
+                } else {
+                    // 盤面の幅を超える数字も不正な文字として扱う
+                    let d = Digit::try_from(c)
+                        .ok()
+                        .filter(|d| d.get() as usize <= width)
+                        .ok_or(BoardParseError::InvalidCharacter {
+                            line,
+                            column,
+                            found: c,
+                        })?;
+                    Some(d)
+                };
+                items.push(Square::new(d, row, col));
+            }
         }
 
-        let num_blocks = height / block_size;
-        Board::new(items, block_size, num_blocks)
+        Ok(Board::new(items, block_height, block_width))
     }
 }
 
@@ -345,12 +897,20 @@ impl<'a> DuplicationValidator<'a> {
         self.validate_with_scope(Scope::Row(item_pos.row), digit)?;
         self.validate_with_scope(Scope::Column(item_pos.col), digit)?;
 
-        let block_pos = BlockPosition {
-            row: item_pos.row / self.board.num_blocks(),
-            col: item_pos.col / self.board.num_blocks(),
-        };
+        let block_pos = self.board.block_position_of(item_pos);
         self.validate_with_scope(Scope::BoardBlock(block_pos), digit)?;
 
+        let index = self.board.item_index(item_pos);
+        for unit in self.board.each_units() {
+            if self.board.units[unit].contains(index) {
+                self.validate_with_scope(Scope::Unit(unit), digit)?;
+            }
+        }
+
+        if let Some(cage) = self.board.cage_of(item_pos) {
+            self.validate_with_scope(Scope::Cage(cage), digit)?;
+        }
+
         Ok(())
     }
 
@@ -367,6 +927,8 @@ impl<'a> DuplicationValidator<'a> {
             Scope::BoardBlock(pos) => {
                 self.validate_with_iter(scope, digit, self.board.block_at(pos))
             }
+            Scope::Unit(unit) => self.validate_with_iter(scope, digit, self.board.unit_items(unit)),
+            Scope::Cage(cage) => self.validate_with_iter(scope, digit, self.board.cage_items(cage)),
         }
     }
 
@@ -411,3 +973,302 @@ impl<'a> DuplicationValidator<'a> {
         }
     }
 }
+
+struct CageValidator<'a> {
+    board: &'a Board<Square>,
+}
+
+impl<'a> CageValidator<'a> {
+    pub fn new(board: &'a Board<Square>) -> Self {
+        CageValidator { board }
+    }
+
+    pub fn validate(&self, cage: usize, digit: Option<Digit>) -> Result<(), BoardError> {
+        let expected = self.board.cages[cage].sum;
+        let digits = self
+            .board
+            .cage_items(cage)
+            .flat_map(|sq| sq.digit())
+            .chain(digit)
+            .collect_vec();
+        let sum = digits.iter().map(|d| d.get() as u32).sum::<u32>();
+
+        // 埋まっていれば合計が一致し、埋まっていなければ合計に達していないこと
+        let is_complete = digits.len() == self.board.cages[cage].cells.len();
+        if (is_complete && sum != expected) || (!is_complete && sum >= expected) {
+            Err(BoardError::CageSum(cage, sum, expected))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::{
+        block_shape, BlockPosition, BoardError, BoardLoader, BoardParseError, ItemPosition, Scope,
+        UnitKind,
+    };
+    use crate::sudoku::digit::Digit;
+
+    const PUZZLE: [&str; 9] = [
+        "003020600",
+        "900305001",
+        "001806400",
+        "008102900",
+        "700000008",
+        "006708200",
+        "002609500",
+        "800203009",
+        "005010300",
+    ];
+
+    #[test]
+    fn test_unique_solution() {
+        let board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        assert_eq!(1, board.count_solutions(10));
+        assert!(board.has_unique_solution());
+    }
+
+    #[test]
+    fn test_multiple_solutions() {
+        let board = BoardLoader::from_lines((0..9).map(|_| "000000000")).unwrap();
+        assert_eq!(0, board.count_solutions(0));
+        assert_eq!(5, board.count_solutions(5));
+        assert!(!board.has_unique_solution());
+    }
+
+    #[test]
+    fn test_no_solution() {
+        let mut lines = PUZZLE;
+        lines[0] = "303020600";
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        assert_eq!(0, board.count_solutions(2));
+        assert!(!board.has_unique_solution());
+    }
+
+    #[test]
+    fn test_blank_characters() {
+        let mut lines = PUZZLE;
+        lines[0] = "._3*2_6.0";
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        assert_eq!(1, board.count_solutions(2));
+        assert!(board
+            .item_at(ItemPosition { row: 0, col: 1 })
+            .digit()
+            .is_none());
+        assert!(board
+            .item_at(ItemPosition { row: 0, col: 3 })
+            .digit()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let board = BoardLoader::from_lines(PUZZLE.iter().take(7).copied());
+        assert_eq!(
+            Err(BoardParseError::WrongSize { height: 7 }),
+            board.map(|_| ())
+        );
+
+        let mut lines = PUZZLE;
+        lines[3] = "00810290";
+        let board = BoardLoader::from_lines(lines.iter().copied());
+        assert_eq!(
+            Err(BoardParseError::WrongLineLength {
+                line: 4,
+                expected: 9,
+                found: 8
+            }),
+            board.map(|_| ())
+        );
+
+        let mut lines = PUZZLE;
+        lines[1] = "900305x01";
+        let board = BoardLoader::from_lines(lines.iter().copied());
+        assert_eq!(
+            Err(BoardParseError::InvalidCharacter {
+                line: 2,
+                column: 7,
+                found: 'x'
+            }),
+            board.map(|_| ())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        use crate::sudoku::board::Board;
+        use crate::sudoku::square::Square;
+
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        board.add_diagonals();
+        let json = serde_json::to_value(&board).unwrap();
+        let restored = serde_json::from_value::<Board<Square>>(json.clone()).unwrap();
+        assert!(board.items().eq(restored.items()));
+        assert_eq!(board.units(), restored.units());
+
+        // 形の合わない盤面は、後でパニックさせずに復元の時点でエラーにする
+        let broken = |f: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = json.clone();
+            f(&mut json);
+            serde_json::from_value::<Board<Square>>(json).is_err()
+        };
+        assert!(broken(&|json| {
+            json["items"].as_array_mut().unwrap().pop();
+        }));
+        assert!(broken(&|json| json["block_width"] = 0.into()));
+        assert!(broken(&|json| json["block_width"] = 4.into()));
+        assert!(broken(&|json| json["units"][0]["cells"][8] = 81.into()));
+        assert!(broken(&|json| json["regions"] = vec![0; 81].into()));
+    }
+
+    #[test]
+    fn test_block_shape() {
+        assert_eq!(Some((2, 2)), block_shape(4));
+        assert_eq!(Some((2, 3)), block_shape(6));
+        assert_eq!(Some((2, 4)), block_shape(8));
+        assert_eq!(Some((3, 3)), block_shape(9));
+        assert_eq!(Some((3, 4)), block_shape(12));
+        assert_eq!(None, block_shape(7));
+
+        // 6x6 は 2行3列のブロックが 3行2列に並ぶ
+        let lines = ["000406", "000020", "230000", "500001", "300005", "040000"];
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        assert_eq!((2, 3), (board.block_height(), board.block_width()));
+        assert_eq!((3, 2), (board.num_block_rows(), board.num_block_columns()));
+        assert_eq!(
+            BlockPosition { row: 2, col: 1 },
+            board.block_position_of(ItemPosition { row: 5, col: 3 })
+        );
+        assert_eq!(5, board.block_index(BlockPosition { row: 2, col: 1 }));
+
+        let digits = board
+            .block_at(BlockPosition { row: 0, col: 1 })
+            .map(|sq| sq.digit().map_or(0, |d| d.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![4, 0, 6, 0, 2, 0], digits);
+        assert!(board.has_unique_solution());
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        assert!(board.validate().is_ok());
+
+        // r3c3 と r4c4 がどちらも 1 なので、対角線を足すと重複になる
+        board.add_diagonals();
+        assert_eq!(2, board.units().len());
+        assert!(board
+            .peer_positions(ItemPosition { row: 0, col: 0 })
+            .contains(80));
+        assert!(matches!(
+            board.validate(),
+            Err(BoardError::Duplication(Scope::Unit(0), _))
+        ));
+    }
+
+    #[test]
+    fn test_cages() {
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        // r1c1 と r1c2 は 4 と 8 になる
+        let cells = vec![
+            ItemPosition { row: 0, col: 0 },
+            ItemPosition { row: 0, col: 1 },
+        ];
+        board.add_cage(12, cells.clone());
+        assert_eq!(Some(0), board.cage_of(ItemPosition { row: 0, col: 1 }));
+        assert_eq!(None, board.cage_of(ItemPosition { row: 0, col: 2 }));
+        assert!(board
+            .peer_positions(ItemPosition { row: 0, col: 0 })
+            .contains(1));
+        assert!(board.validate().is_ok());
+        assert!(board.has_unique_solution());
+
+        // 合計を超える数字は置けない
+        board.fix_digit_at(cells[1], Digit::from(8)).unwrap();
+        assert!(matches!(
+            board.fix_digit_at(cells[0], Digit::from(5)),
+            Err(BoardError::CageSum(0, 13, 12))
+        ));
+        board.fix_digit_at(cells[0], Digit::from(4)).unwrap();
+        assert!(board.validate().is_ok());
+
+        // 合計の合わないケージがあると解がなくなる
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        board.add_cage(13, cells);
+        assert_eq!(0, board.count_solutions(2));
+    }
+
+    #[test]
+    fn test_regions() {
+        let lines = ["1002", "0000", "0010", "0000"];
+        let mut board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        assert!(!board.is_jigsaw());
+
+        // 各行をそのままブロックにする
+        board.set_regions(vec![0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3]);
+        assert!(board.is_jigsaw());
+        assert_eq!(
+            BlockPosition { row: 1, col: 0 },
+            board.block_position_of(ItemPosition { row: 2, col: 3 })
+        );
+        assert_eq!(
+            (0..4)
+                .map(|col| ItemPosition { row: 1, col })
+                .collect::<Vec<_>>(),
+            board.scope_positions(Scope::BoardBlock(BlockPosition { row: 0, col: 1 }))
+        );
+        assert!(!board
+            .peer_positions(ItemPosition { row: 0, col: 0 })
+            .contains(5));
+        assert!(board.validate().is_ok());
+
+        // r1c1, r1c4, r2c1, r2c4 のブロックでは r2c4 に 1 を置けない
+        board.set_regions(vec![0, 1, 1, 0, 0, 1, 1, 0, 2, 3, 3, 2, 2, 3, 3, 2]);
+        assert!(board.validate().is_ok());
+        assert!(matches!(
+            board.fix_digit_at(ItemPosition { row: 1, col: 3 }, Digit::from(1)),
+            Err(BoardError::Duplication(Scope::BoardBlock(_), _))
+        ));
+    }
+
+    #[test]
+    fn test_windows() {
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        board.add_windows();
+        assert_eq!(4, board.units().len());
+        assert!(board.units().iter().all(|u| u.kind() == UnitKind::Window));
+        assert_eq!(
+            &[10, 11, 12, 19, 20, 21, 28, 29, 30],
+            board.units()[0].cells()
+        );
+        assert_eq!(
+            &[50, 51, 52, 59, 60, 61, 68, 69, 70],
+            board.units()[3].cells()
+        );
+
+        // 最初の窓には r3c4 と r4c3 の 8 が重複している
+        assert!(matches!(
+            board.validate(),
+            Err(BoardError::Duplication(Scope::Unit(0), _))
+        ));
+
+        // 任意のセルの組を家として追加できる
+        let mut board = BoardLoader::from_lines(PUZZLE.iter().copied()).unwrap();
+        board.add_unit(
+            UnitKind::Custom,
+            (0..9).map(|i| ItemPosition {
+                row: i,
+                col: (i * 4 + 2) % 9,
+            }),
+        );
+        assert_eq!(UnitKind::Custom, board.units()[0].kind());
+        // r4c6 と r8c4 の 2 が重複する
+        assert!(matches!(
+            board.validate(),
+            Err(BoardError::Duplication(Scope::Unit(0), _))
+        ));
+    }
+}
@@ -1,11 +1,13 @@
 use std::fmt;
 use std::iter::FromIterator;
 
+use crate::sudoku::board::ItemPosition;
 use crate::sudoku::digit::Digit;
 use crate::sudoku::set::DigitSet;
 use crate::sudoku::Square;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Candidate {
     digits: DigitSet,
     row: usize,
@@ -13,11 +15,11 @@ pub struct Candidate {
 }
 
 impl Candidate {
-    pub fn new(sq: &Square) -> Self {
+    pub fn new(sq: &Square, size: usize) -> Self {
         let digits = if sq.digit().is_some() {
             DigitSet::default()
         } else {
-            DigitSet::from_iter(Digit::all_digits_iter())
+            DigitSet::from_iter(Digit::range_iter(size))
         };
         Candidate {
             digits,
@@ -26,12 +28,39 @@ impl Candidate {
         }
     }
 
+    pub fn position(&self) -> ItemPosition {
+        ItemPosition {
+            row: self.row,
+            col: self.column,
+        }
+    }
+
+    pub fn with_digits(digits: DigitSet, row: usize, column: usize) -> Self {
+        Candidate {
+            digits,
+            row,
+            column,
+        }
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
     pub fn remove(&mut self, digit: Digit) -> bool {
         self.digits.remove(digit)
     }
 
     pub fn remove_iter(&mut self, digits: impl Iterator<Item = Digit>) -> bool {
-        digits.fold(false, |updated, d| self.remove(d) || updated)
+        digits.fold(false, |updated, d| self.remove(d) | updated)
+    }
+
+    pub fn clear(&mut self) {
+        self.digits.clear();
     }
 
     pub fn has_candidate(&self) -> bool {
@@ -43,11 +72,7 @@ impl Candidate {
     }
 
     pub fn is_fixed(&self) -> bool {
-        if self.digits.len() == 1 {
-            true
-        } else {
-            false
-        }
+        self.digits.len() == 1
     }
 
     pub fn take_fixed_digit(&mut self) -> Option<Digit> {
@@ -1,16 +1,18 @@
 use std::fmt;
 
 use anyhow::{anyhow, Error as AnyhowError};
-use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::fmt::Debug;
 
 const MIN: u8 = 1;
-const MAX: u8 = 9;
+pub(crate) const MAX: u8 = 25;
 
-const RADIX: u32 = 10;
+// 10 以上は 16進数と同じく A から順に英字で表す、25x25 なら P まで
+const SYMBOLS: &[u8; MAX as usize] = b"123456789ABCDEFGHIJKLMNOP";
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u32", into = "u32"))]
 pub struct Digit(u8);
 
 impl Digit {
@@ -18,29 +20,32 @@ impl Digit {
         self.0
     }
 
-    pub fn all_digits() -> BTreeSet<Digit> {
-        (MIN..=MAX).map(Digit::from).collect()
-    }
-
-    pub fn all_digits_iter() -> DigitIter {
-        DigitIter::default()
-    }
-
-    pub fn exclude_iter(digit: Digit) -> impl Iterator<Item = Digit> {
-        DigitIter::default().filter(move |&d| d != digit)
+    // 盤面の幅に応じた 1..=size の数字
+    pub fn range_iter(size: usize) -> DigitIter {
+        assert!(size <= MAX as usize);
+        DigitIter {
+            current: 0,
+            size: size as u8,
+        }
     }
 }
 
 impl From<u8> for Digit {
     fn from(value: u8) -> Self {
-        assert!(value >= MIN && value <= MAX);
+        assert!((MIN..=MAX).contains(&value));
         Digit(value)
     }
 }
 
-impl Into<char> for Digit {
-    fn into(self) -> char {
-        std::char::from_digit(self.0 as u32, RADIX).unwrap()
+impl From<Digit> for u32 {
+    fn from(digit: Digit) -> Self {
+        digit.0 as u32
+    }
+}
+
+impl From<Digit> for char {
+    fn from(digit: Digit) -> Self {
+        char::from(SYMBOLS[digit.0 as usize - 1])
     }
 }
 
@@ -48,10 +53,12 @@ impl TryFrom<char> for Digit {
     type Error = AnyhowError;
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
-        let digit = value
-            .to_digit(10)
+        let c = value.to_ascii_uppercase();
+        let index = SYMBOLS
+            .iter()
+            .position(|s| char::from(*s) == c)
             .ok_or_else(|| anyhow!("Cannot convert '{}' to Digit", value))?;
-        Self::try_from(digit)
+        Ok(Digit(index as u8 + MIN))
     }
 }
 
@@ -60,7 +67,7 @@ impl TryFrom<u32> for Digit {
 
     fn try_from(value: u32) -> Result<Self, Self::Error> {
         let digit = Some(u8::try_from(value)?)
-            .filter(|&n| n >= MIN && n <= MAX)
+            .filter(|n| (MIN..=MAX).contains(n))
             .ok_or_else(|| {
                 anyhow!(
                     "out of range. must be within {} to {}, given:{}",
@@ -75,20 +82,21 @@ impl TryFrom<u32> for Digit {
 
 impl fmt::Display for Digit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self.0, f)
+        fmt::Display::fmt(&char::from(*self), f)
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone)]
 pub struct DigitIter {
     current: u8,
+    size: u8,
 }
 
 impl Iterator for DigitIter {
     type Item = Digit;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current < MAX {
+        if self.current < self.size {
             self.current += 1;
             Some(Digit::from(self.current))
         } else {
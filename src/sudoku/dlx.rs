@@ -0,0 +1,385 @@
+use crate::sudoku::board::{Board, Cage};
+use crate::sudoku::digit::Digit;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::solver::{SolverError, Statistics};
+use crate::sudoku::Square;
+
+const ROOT: usize = 0;
+
+// 探索中に分岐した回数と、別の候補を試し直した回数
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct SearchCounts {
+    pub(crate) branches: usize,
+    pub(crate) backtracks: usize,
+}
+
+struct DancingLinks {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    rows: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl DancingLinks {
+    fn new(num_columns: usize) -> Self {
+        // 0 番目がルート、1..=num_columns が列ヘッダ
+        let n = num_columns + 1;
+        DancingLinks {
+            left: (0..n).map(|i| (i + n - 1) % n).collect(),
+            right: (0..n).map(|i| (i + 1) % n).collect(),
+            up: (0..n).collect(),
+            down: (0..n).collect(),
+            column: (0..n).collect(),
+            rows: vec![usize::MAX; n],
+            sizes: vec![0; n],
+        }
+    }
+
+    fn add_row(&mut self, row: usize, columns: &[usize]) {
+        let first = self.left.len();
+        for (k, c) in columns.iter().enumerate() {
+            let col = c + 1;
+            let node = first + k;
+
+            // 列の末尾に追加する
+            let last = self.up[col];
+            self.up.push(last);
+            self.down.push(col);
+            self.down[last] = node;
+            self.up[col] = node;
+            self.column.push(col);
+            self.rows.push(row);
+            self.sizes[col] += 1;
+
+            // 行の末尾に追加する
+            if k == 0 {
+                self.left.push(node);
+                self.right.push(node);
+            } else {
+                self.left.push(node - 1);
+                self.right.push(first);
+                self.right[node - 1] = node;
+                self.left[first] = node;
+            }
+        }
+    }
+
+    fn cover(&mut self, col: usize) {
+        self.right[self.left[col]] = self.right[col];
+        self.left[self.right[col]] = self.left[col];
+
+        let mut i = self.down[col];
+        while i != col {
+            let mut j = self.right[i];
+            while j != i {
+                self.down[self.up[j]] = self.down[j];
+                self.up[self.down[j]] = self.up[j];
+                self.sizes[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, col: usize) {
+        let mut i = self.up[col];
+        while i != col {
+            let mut j = self.left[i];
+            while j != i {
+                self.sizes[self.column[j]] += 1;
+                self.down[self.up[j]] = j;
+                self.up[self.down[j]] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        self.right[self.left[col]] = col;
+        self.left[self.right[col]] = col;
+    }
+
+    fn choose_column(&self) -> usize {
+        let mut best = self.right[ROOT];
+        let mut col = self.right[best];
+        while col != ROOT {
+            if self.sizes[col] < self.sizes[best] {
+                best = col;
+            }
+            col = self.right[col];
+        }
+        best
+    }
+
+    fn search<P, F>(
+        &mut self,
+        partial: &mut Vec<usize>,
+        counts: &mut SearchCounts,
+        prune: &mut P,
+        on_solution: &mut F,
+    ) -> bool
+    where
+        P: FnMut(&[usize]) -> bool,
+        F: FnMut(&[usize]) -> bool,
+    {
+        if self.right[ROOT] == ROOT {
+            return on_solution(partial);
+        }
+
+        // 候補の一番少ない列から試す
+        let col = self.choose_column();
+        if self.sizes[col] == 0 {
+            return true;
+        }
+        if self.sizes[col] > 1 {
+            counts.branches += 1;
+        }
+
+        self.cover(col);
+        let mut continues = true;
+        let mut row = self.down[col];
+        while row != col {
+            if row != self.down[col] {
+                counts.backtracks += 1;
+            }
+
+            partial.push(self.rows[row]);
+            if prune(partial) {
+                partial.pop();
+                row = self.down[row];
+                continue;
+            }
+
+            let mut j = self.right[row];
+            while j != row {
+                self.cover(self.column[j]);
+                j = self.right[j];
+            }
+
+            continues = self.search(partial, counts, prune, on_solution);
+
+            let mut j = self.left[row];
+            while j != row {
+                self.uncover(self.column[j]);
+                j = self.left[j];
+            }
+            partial.pop();
+
+            if !continues {
+                break;
+            }
+            row = self.down[row];
+        }
+        self.uncover(col);
+
+        continues
+    }
+}
+
+pub(crate) struct ExactCover {
+    links: DancingLinks,
+    size: usize,
+    cages: Vec<Cage>,
+    counts: SearchCounts,
+}
+
+impl ExactCover {
+    pub(crate) fn new(board: &Board<Square>) -> Self {
+        // 制約: セル、行×数字、列×数字、ブロック×数字、追加のユニット×数字
+        let size = board.width();
+        let num_cells = size * size;
+        let mut links = DancingLinks::new(4 * num_cells + board.units().len() * size);
+
+        for pos in board.item_positions() {
+            let cell = board.item_index(pos);
+            let block = board.block_index(board.block_position_of(pos));
+
+            let fixed = board.item_at(pos).digit();
+            for d in (0..size).filter(|d| fixed.map_or(true, |f| f.get() as usize == d + 1)) {
+                let mut columns = vec![
+                    cell,
+                    num_cells + pos.row * size + d,
+                    2 * num_cells + pos.col * size + d,
+                    3 * num_cells + block * size + d,
+                ];
+                for (i, unit) in board.units().iter().enumerate() {
+                    if unit.contains(cell) {
+                        columns.push(4 * num_cells + i * size + d);
+                    }
+                }
+                links.add_row(cell * size + d, &columns);
+            }
+        }
+
+        ExactCover {
+            links,
+            size,
+            cages: board.cages().to_vec(),
+            counts: SearchCounts::default(),
+        }
+    }
+
+    pub(crate) fn counts(&self) -> SearchCounts {
+        self.counts
+    }
+
+    pub(crate) fn search<F>(&mut self, mut on_solution: F)
+    where
+        F: FnMut(&[(usize, Digit)]) -> bool,
+    {
+        let ExactCover {
+            links,
+            size,
+            cages,
+            counts,
+        } = self;
+        let size = *size;
+        let mut partial = Vec::new();
+        let mut prune = |rows: &[usize]| violates_cage(cages, size, rows);
+        links.search(&mut partial, counts, &mut prune, &mut |rows: &[usize]| {
+            let digits = rows
+                .iter()
+                .map(|row| (row / size, Digit::from((row % size + 1) as u8)))
+                .collect::<Vec<_>>();
+            on_solution(&digits)
+        });
+    }
+}
+
+// 最後に置いた数字のケージで、重複や合計の超過・不足が起きていないか調べる
+fn violates_cage(cages: &[Cage], size: usize, rows: &[usize]) -> bool {
+    let cell = match rows.last() {
+        Some(row) => row / size,
+        None => return false,
+    };
+    let cage = match cages.iter().find(|cage| cage.contains(cell)) {
+        Some(cage) => cage,
+        None => return false,
+    };
+
+    let mut digits = DigitSet::default();
+    let mut sum = 0;
+    for row in rows.iter().filter(|row| cage.contains(*row / size)) {
+        let d = Digit::from((row % size + 1) as u8);
+        if digits.contains(d) {
+            return true;
+        }
+        digits.set(d);
+        sum += d.get() as u32;
+    }
+
+    // 残りのセルに置ける最小・最大の合計で見込みを判定する
+    let rest = (cage.cells().len() - digits.len()) as u32;
+    let min = rest * (rest + 1) / 2;
+    let max = (0..rest).map(|i| size as u32 - i).sum::<u32>();
+    sum + min > cage.sum() || sum + max < cage.sum()
+}
+
+pub struct DlxSolver {
+    board: Board<Square>,
+    statistics: Statistics,
+}
+
+impl DlxSolver {
+    pub fn new(board: Board<Square>) -> Self {
+        DlxSolver {
+            board,
+            statistics: Statistics::default(),
+        }
+    }
+
+    pub fn board(&self) -> &Board<Square> {
+        &self.board
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        self.statistics.clone()
+    }
+
+    pub fn solve(&mut self) -> Result<bool, SolverError> {
+        self.board.validate()?;
+
+        let mut solution = None;
+        let mut cover = ExactCover::new(&self.board);
+        cover.search(|digits| {
+            solution = Some(digits.to_vec());
+            false
+        });
+
+        let counts = cover.counts();
+        self.statistics.get_stuck += counts.branches;
+        self.statistics.back_tracked += counts.backtracks;
+
+        match solution {
+            Some(digits) => {
+                for (cell, digit) in digits {
+                    let pos = self.board.item_position(cell);
+                    if !self.board.item_at(pos).is_fixed() {
+                        self.board.fix_digit_at(pos, digit)?;
+                    }
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::sudoku::dlx::DlxSolver;
+    use crate::sudoku::{BoardLoader, Solver};
+
+    #[test]
+    fn test_hardest() {
+        let board = BoardLoader::from_lines(
+            [
+                "800000000",
+                "003600000",
+                "070090200",
+                "050007000",
+                "000045700",
+                "000100030",
+                "001000068",
+                "008500010",
+                "090000400",
+            ]
+            .iter()
+            .copied(),
+        )
+        .unwrap();
+
+        let mut solver = DlxSolver::new(board);
+        assert!(solver.solve().unwrap());
+        assert!(solver.board().is_complete());
+
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| sq.digit().unwrap().get().to_string())
+            .join("");
+        assert_eq!(
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452",
+            digits
+        );
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let s = include_str!("../../assets/p096_sudoku.txt");
+        let boards = BoardLoader::load(s).unwrap().into_iter().take(10);
+
+        for board in boards {
+            let mut reference = DlxSolver::new(board.clone());
+            assert!(reference.solve().unwrap());
+
+            let mut solver = Solver::new(board);
+            solver.solve();
+            assert!(solver.board().items().eq(reference.board().items()));
+        }
+    }
+}
@@ -1,45 +1,120 @@
 use std::collections::VecDeque;
+use std::iter::FromIterator;
 
-use crate::sudoku::action::{RemoveAction, RetainAction};
+use crate::sudoku::action::{ActionScope, RemoveAction, RetainAction};
+use crate::sudoku::board::ItemPosition;
 use crate::sudoku::candidate::Candidate;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::step::StepKind;
 use crate::sudoku::Board;
 
 #[derive(Debug, Copy, Clone)]
-pub enum Event {
-    RetainAction(RetainAction),
-    RemoveAction(RemoveAction),
+enum Action {
+    Retain(RetainAction),
+    Remove(RemoveAction),
+}
+
+// フィルタが積む候補の更新、どのフィルタがどのセル・数字を根拠にしたかも持つ
+#[derive(Debug, Clone)]
+pub struct Event {
+    filter: &'static str,
+    action: Action,
+    pattern: Vec<ItemPosition>,
+    pattern_digits: DigitSet,
 }
 
 impl From<RetainAction> for Event {
     fn from(action: RetainAction) -> Self {
-        Event::RetainAction(action)
+        Event::new(Action::Retain(action))
     }
 }
 
 impl From<RemoveAction> for Event {
     fn from(action: RemoveAction) -> Self {
-        Event::RemoveAction(action)
+        Event::new(Action::Remove(action))
     }
 }
 
 impl Event {
-    pub fn evaluate(&mut self, candidates: &mut Board<Candidate>) -> bool {
-        match *self {
-            Event::RetainAction(action) => action.retain(candidates),
-            Event::RemoveAction(action) => action.remove(candidates),
+    fn new(action: Action) -> Self {
+        Event {
+            filter: "",
+            action,
+            pattern: Vec::new(),
+            pattern_digits: DigitSet::default(),
+        }
+    }
+
+    // 根拠になったセルと数字 (Naked Pair のペア、X-Wing の四隅など) を添える
+    pub(crate) fn with_pattern(
+        mut self,
+        cells: impl IntoIterator<Item = ItemPosition>,
+        digits: DigitSet,
+    ) -> Self {
+        self.pattern = cells.into_iter().collect();
+        self.pattern_digits = digits;
+        self
+    }
+
+    pub fn filter(&self) -> &'static str {
+        self.filter
+    }
+
+    pub fn kind(&self) -> StepKind {
+        match self.action {
+            Action::Retain(_) => StepKind::Retain,
+            Action::Remove(_) => StepKind::Remove,
+        }
+    }
+
+    pub fn scope(&self) -> ActionScope {
+        match self.action {
+            Action::Retain(action) => action.scope(),
+            Action::Remove(action) => action.scope(),
+        }
+    }
+
+    pub fn digits(&self) -> DigitSet {
+        match self.action {
+            Action::Retain(action) => action.digits(),
+            Action::Remove(action) => DigitSet::from_iter(Some(action.digit())),
+        }
+    }
+
+    pub fn pattern(&self) -> &[ItemPosition] {
+        &self.pattern
+    }
+
+    pub fn pattern_digits(&self) -> DigitSet {
+        self.pattern_digits
+    }
+
+    pub fn evaluate(&self, candidates: &mut Board<Candidate>) -> bool {
+        match self.action {
+            Action::Retain(action) => action.retain(candidates),
+            Action::Remove(action) => action.remove(candidates),
         }
     }
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct EventQueue(VecDeque<Event>);
+pub struct EventQueue {
+    events: VecDeque<Event>,
+    filter: &'static str,
+}
 
 impl EventQueue {
-    pub fn push_back(&mut self, event: Event) {
-        self.0.push_back(event);
+    // 以降に積まれるイベントに、このフィルタ名を付ける
+    pub fn set_filter(&mut self, filter: &'static str) {
+        self.filter = filter;
+    }
+
+    pub fn push_back(&mut self, mut event: Event) {
+        event.filter = self.filter;
+        self.events.push_back(event);
     }
 
     pub fn pop_front(&mut self) -> Option<Event> {
-        self.0.pop_front()
+        self.events.pop_front()
     }
 }
@@ -1,23 +1,34 @@
+mod chain;
+mod fish;
 mod hidden;
 mod intersection;
+mod killer;
+mod naked;
 mod single;
+mod wing;
 
 use std::collections::BTreeMap;
 
+use crate::sudoku::action::{ActionScope, RemoveAction, RetainAction};
+use crate::sudoku::board::ItemPosition;
 use crate::sudoku::candidate::Candidate;
 use crate::sudoku::digit::Digit;
-use crate::sudoku::event::EventQueue;
-use crate::sudoku::positions::Positions;
+use crate::sudoku::event::{Event, EventQueue};
+use crate::sudoku::positions::{CellPositions, Positions};
 use crate::sudoku::set::DigitSet;
 use crate::sudoku::{Board, Square};
 
+pub use chain::{AlternatingInferenceChain, SimpleColoring, XChain, XYChain};
+pub use fish::Fish;
 pub use hidden::{HiddenPair, HiddenQuad, HiddenTriple};
 pub use intersection::{LockedCandidateClaiming, LockedCandidatePointing};
+pub use killer::{CageCombination, RuleOf45};
+pub use naked::{NakedPair, NakedQuad, NakedTriple};
 pub use single::{NakedSingle, SingleCandidate};
+pub use wing::{WWing, XYWing, XYZWing};
 
 #[derive(Default)]
 pub struct FilterContext {
-    digits_in_use: DigitSet,
     digit_positions: BTreeMap<Digit, Positions>,
 }
 
@@ -28,10 +39,8 @@ impl FilterContext {
     ) {
         self.digit_positions.clear();
         for (pos, c) in candidates.enumerate() {
-            for d in Digit::all_digits_iter() {
-                if c.contains(d) {
-                    self.digit_positions.entry(d).or_default().set(pos);
-                }
+            for d in c.digits_iter() {
+                self.digit_positions.entry(d).or_default().set(pos);
             }
         }
     }
@@ -79,10 +88,54 @@ impl<'a> FilterInput<'a> {
             candidates,
         }
     }
+
+    pub fn board(&self) -> &Board<Square> {
+        self.board
+    }
+
+    pub fn candidates(&self) -> &Board<Candidate> {
+        self.candidates
+    }
+
+    fn cell_positions(&self, cells: impl IntoIterator<Item = ItemPosition>) -> CellPositions {
+        cells
+            .into_iter()
+            .map(|pos| self.board.item_index(pos))
+            .collect()
+    }
+
+    // 外部のフィルタ向け、指定したセルから数字を除外する
+    pub fn remove_candidates(
+        &mut self,
+        digit: Digit,
+        cells: impl IntoIterator<Item = ItemPosition>,
+    ) {
+        let scope = ActionScope::Cells(self.cell_positions(cells));
+        let event = Event::from(RemoveAction::new(digit, scope));
+        self.event_queue.push_back(event);
+    }
+
+    // 外部のフィルタ向け、指定したセルの候補を digits だけに絞る
+    pub fn retain_candidates(
+        &mut self,
+        digits: DigitSet,
+        cells: impl IntoIterator<Item = ItemPosition>,
+    ) {
+        let scope = ActionScope::Cells(self.cell_positions(cells));
+        let event = Event::from(RetainAction::new(digits, scope));
+        self.event_queue.push_back(event);
+    }
 }
 
+// 難易度を指定しない外部のフィルタは、中程度の手法として扱う
+pub const DEFAULT_DIFFICULTY: f32 = 5.0;
+
 pub trait NamedFilter {
     fn name(&self) -> &'static str;
+
+    fn difficulty(&self) -> f32 {
+        DEFAULT_DIFFICULTY
+    }
 }
 
 pub trait FilterCandidates: NamedFilter {
@@ -93,6 +146,8 @@ pub trait ScanCandidates: FilterCandidates {
     fn scan_rows(&self, input: &mut FilterInput);
     fn scan_columns(&self, input: &mut FilterInput);
     fn scan_blocks(&self, input: &mut FilterInput);
+    // 追加のユニットを調べないフィルタは実装しなくてよい
+    fn scan_units(&self, _input: &mut FilterInput) {}
 }
 
 impl<T: ScanCandidates> FilterCandidates for T {
@@ -100,5 +155,63 @@ impl<T: ScanCandidates> FilterCandidates for T {
         self.scan_rows(&mut input);
         self.scan_columns(&mut input);
         self.scan_blocks(&mut input);
+        self.scan_units(&mut input);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::event::EventQueue;
+    use crate::sudoku::filter::{FilterCandidates, FilterContext, FilterInput, NakedSingle};
+    use crate::sudoku::{Board, BoardLoader, Square};
+
+    pub(crate) fn apply_filter<F: FilterCandidates + ?Sized>(
+        filter: &F,
+        board: &Board<Square>,
+        candidates: &mut Board<Candidate>,
+    ) -> bool {
+        let mut context = FilterContext::default();
+        let mut event_queue = EventQueue::default();
+        filter.filter_candidates(FilterInput::new(
+            &mut context,
+            &mut event_queue,
+            board,
+            candidates,
+        ));
+
+        let mut evaluated = false;
+        while let Some(event) = event_queue.pop_front() {
+            evaluated |= event.evaluate(candidates);
+        }
+        evaluated
+    }
+
+    pub(crate) fn apply_filters(
+        filters: &[&dyn FilterCandidates],
+        board: &mut Board<Square>,
+        candidates: &mut Board<Candidate>,
+    ) {
+        while filters
+            .iter()
+            .any(|filter| apply_filter(*filter, board, candidates))
+        {
+            for pos in candidates.item_positions().collect::<Vec<_>>() {
+                if let Some(digit) = candidates.take_fixed_digit_at(pos) {
+                    board.fix_digit_at(pos, digit).unwrap();
+                }
+            }
+        }
+    }
+
+    pub(crate) fn load_candidates(lines: &[&str]) -> (Board<Square>, Board<Candidate>) {
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let items = board
+            .items()
+            .map(|sq| Candidate::new(sq, board.width()))
+            .collect();
+        let mut candidates = board.with_items(items);
+        apply_filter(&NakedSingle, &board, &mut candidates);
+        (board, candidates)
     }
 }
@@ -0,0 +1,210 @@
+mod aic;
+mod coloring;
+mod graph;
+
+use crate::sudoku::action::{ActionScope, RemoveAction, RetainAction};
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::{Event, EventQueue};
+use crate::sudoku::positions::CellPositions;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::Board;
+
+use graph::Node;
+
+pub use aic::{AlternatingInferenceChain, XChain, XYChain};
+pub use coloring::SimpleColoring;
+
+#[derive(Debug, Copy, Clone)]
+enum Conclusion {
+    Place(Node),
+    Remove(Digit, CellPositions),
+}
+
+impl Conclusion {
+    fn into_event(self) -> Event {
+        match self {
+            Conclusion::Place(node) => {
+                let cells = Some(node.cell).into_iter().collect();
+                Event::from(RetainAction::with_digit(
+                    node.digit,
+                    ActionScope::Cells(cells),
+                ))
+            }
+            Conclusion::Remove(digit, cells) => {
+                Event::from(RemoveAction::new(digit, ActionScope::Cells(cells)))
+            }
+        }
+    }
+}
+
+// chain は結論の根拠になったノード、ステップの表示に使う
+fn push_conclusions(
+    event_queue: &mut EventQueue,
+    candidates: &Board<Candidate>,
+    conclusions: Vec<Conclusion>,
+    chain: &[Node],
+) {
+    let cells = chain
+        .iter()
+        .map(|node| candidates.item_position(node.cell))
+        .collect::<Vec<_>>();
+    let digits = chain.iter().map(|node| node.digit).collect::<DigitSet>();
+    for conclusion in conclusions {
+        let event = conclusion.into_event().with_pattern(cells.clone(), digits);
+        event_queue.push_back(event);
+    }
+}
+
+struct ChainContext<'a> {
+    candidates: &'a Board<Candidate>,
+    peers: Vec<CellPositions>,
+}
+
+impl<'a> ChainContext<'a> {
+    fn new(candidates: &'a Board<Candidate>) -> Self {
+        let peers = candidates
+            .item_positions()
+            .map(|pos| candidates.peer_positions(pos))
+            .collect();
+        ChainContext { candidates, peers }
+    }
+
+    fn candidate_at(&self, cell: usize) -> &Candidate {
+        self.candidates.item_at(self.candidates.item_position(cell))
+    }
+
+    fn cells_with_digit(&self, cells: CellPositions, digit: Digit) -> CellPositions {
+        cells
+            .iter()
+            .filter(|cell| self.candidate_at(*cell).contains(digit))
+            .collect()
+    }
+
+    fn remove(&self, digit: Digit, cells: CellPositions) -> Option<Conclusion> {
+        let cells = self.cells_with_digit(cells, digit);
+        if cells.is_empty() {
+            None
+        } else {
+            Some(Conclusion::Remove(digit, cells))
+        }
+    }
+
+    fn common_weak_targets(&self, a: Node, b: Node) -> Vec<Conclusion> {
+        // a, b の少なくとも一方が真 → 両方と弱リンクで結ばれる候補は偽
+        if a.digit == b.digit {
+            let cells = self.peers[a.cell].and(&self.peers[b.cell]);
+            self.remove(a.digit, cells).into_iter().collect()
+        } else if a.cell == b.cell {
+            let cells = Some(a.cell).into_iter().collect();
+            self.candidate_at(a.cell)
+                .digits_iter()
+                .filter(|d| *d != a.digit && *d != b.digit)
+                .filter_map(|d| self.remove(d, cells))
+                .collect()
+        } else if self.peers[a.cell].contains(b.cell) {
+            let a_cells = Some(a.cell).into_iter().collect();
+            let b_cells = Some(b.cell).into_iter().collect();
+            self.remove(b.digit, a_cells)
+                .into_iter()
+                .chain(self.remove(a.digit, b_cells))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::digit::Digit;
+    use crate::sudoku::filter::tests::{apply_filters, load_candidates};
+    use crate::sudoku::filter::{
+        AlternatingInferenceChain, FilterCandidates, HiddenPair, LockedCandidateClaiming,
+        LockedCandidatePointing, NakedPair, NakedSingle, SimpleColoring, SingleCandidate, XChain,
+        XYChain,
+    };
+    use crate::sudoku::{Board, Square};
+    use itertools::Itertools;
+    use std::convert::TryFrom;
+
+    fn assert_sound(solution: &str, board: &Board<Square>, candidates: &Board<Candidate>) {
+        for (pos, c) in board.item_positions().zip(solution.chars()) {
+            let digit = Digit::try_from(c).unwrap();
+            let fixed = board.item_at(pos).digit() == Some(digit);
+            assert!(
+                fixed || candidates.item_at(pos).contains(digit),
+                "{} lost its solution digit {}",
+                pos,
+                digit
+            );
+        }
+    }
+
+    fn solve_with(puzzle: &str, solution: &str, chain: Option<&dyn FilterCandidates>) -> bool {
+        let lines = puzzle.chars().chunks(9);
+        let lines = lines
+            .into_iter()
+            .map(|s| s.collect::<String>())
+            .collect_vec();
+        let lines = lines.iter().map(|s| s.as_str()).collect_vec();
+        let (mut board, mut candidates) = load_candidates(&lines);
+
+        let mut filters: Vec<&dyn FilterCandidates> = vec![
+            &NakedSingle,
+            &SingleCandidate,
+            &LockedCandidatePointing,
+            &LockedCandidateClaiming,
+            &NakedPair,
+            &HiddenPair,
+        ];
+        filters.extend(chain);
+        apply_filters(&filters, &mut board, &mut candidates);
+
+        assert_sound(solution, &board, &candidates);
+        board.is_complete()
+    }
+
+    fn assert_solved_with(puzzle: &str, solution: &str, chain: &dyn FilterCandidates) {
+        assert!(!solve_with(puzzle, solution, None));
+        assert!(solve_with(puzzle, solution, Some(chain)));
+    }
+
+    #[test]
+    fn test_simple_coloring() {
+        assert_solved_with(
+            "000870100702000004860000050030000000000008300004020010500000700003089000900600500",
+            "345872196712965834869314257238196475197458362654723918586231749473589621921647583",
+            &SimpleColoring,
+        );
+    }
+
+    #[test]
+    fn test_x_chain() {
+        assert_solved_with(
+            "000418000000600000080027050020000070005000000006070209600000980000040015049001000",
+            "562418793137695842984327156423169578795283461816574239651732984278946315349851627",
+            &XChain,
+        );
+    }
+
+    #[test]
+    fn test_xy_chain() {
+        assert_solved_with(
+            "008000000900000200130008400000206000007000300060000510000600087040800100300020005",
+            "658492731974315268132768459591236874427581396863974512215643987746859123389127645",
+            &XYChain,
+        );
+    }
+
+    #[test]
+    fn test_alternating_inference_chain() {
+        let puzzle =
+            "050800000001470000700006010004020009900708304000100000600000040000000087302000000";
+        let solution =
+            "459831762261475938738296415874623159916758324523149876687912543195364287342587691";
+        assert!(!solve_with(puzzle, solution, Some(&XYChain)));
+        assert_solved_with(puzzle, solution, &AlternatingInferenceChain);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::EventQueue;
+use crate::sudoku::filter::chain::graph::{LinkGraph, LinkKind, Node};
+use crate::sudoku::filter::chain::{push_conclusions, ChainContext, Conclusion};
+use crate::sudoku::filter::{FilterCandidates, FilterInput, NamedFilter};
+use crate::sudoku::Board;
+
+const MAX_CHAIN_LENGTH: usize = 16;
+
+struct ChainSearch<'a> {
+    context: ChainContext<'a>,
+    graph: LinkGraph,
+}
+
+impl<'a> ChainSearch<'a> {
+    fn search(&self) -> Option<(Vec<Conclusion>, Vec<Node>)> {
+        (0..self.graph.len()).find_map(|start| self.search_from(start))
+    }
+
+    // 幅優先探索の親を辿って、始点から key までのチェーンを復元する
+    fn chain(
+        &self,
+        mut key: (usize, bool),
+        parents: &BTreeMap<(usize, bool), (usize, bool)>,
+    ) -> Vec<Node> {
+        let mut nodes = vec![self.graph.node(key.0)];
+        while let Some(parent) = parents.get(&key) {
+            nodes.push(self.graph.node(parent.0));
+            key = *parent;
+        }
+        nodes.reverse();
+        nodes
+    }
+
+    fn search_from(&self, start: usize) -> Option<(Vec<Conclusion>, Vec<Node>)> {
+        // 始点を偽と仮定し、(ノード, 強リンクで到達したか) ごとに幅優先で交互に辿る
+        let root = (start, false);
+        let mut parents = BTreeMap::new();
+        let mut visited = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(root);
+        queue.push_back((root, 1));
+
+        while let Some(((index, strong), length)) = queue.pop_front() {
+            if strong {
+                // 始点が偽なら始点が真になる → 始点で確定
+                if index == start {
+                    let conclusions = vec![Conclusion::Place(self.graph.node(start))];
+                    return Some((conclusions, self.chain((index, strong), &parents)));
+                }
+
+                // 始点か終点の少なくとも一方が真
+                let conclusions = self
+                    .context
+                    .common_weak_targets(self.graph.node(start), self.graph.node(index));
+                if !conclusions.is_empty() {
+                    return Some((conclusions, self.chain((index, strong), &parents)));
+                }
+
+                if self.graph.is_weakly_linked(index, start) {
+                    let conclusions = self.loop_conclusions(start, index, &parents);
+                    if !conclusions.is_empty() {
+                        return Some((conclusions, self.chain((index, strong), &parents)));
+                    }
+                }
+            }
+
+            if length >= MAX_CHAIN_LENGTH {
+                continue;
+            }
+
+            let links = if strong {
+                self.graph.weak_links(index).collect::<Vec<_>>()
+            } else {
+                self.graph.strong_links(index).collect::<Vec<_>>()
+            };
+            for next in links {
+                let key = (next, !strong);
+                if visited.insert(key) {
+                    parents.insert(key, (index, strong));
+                    queue.push_back((key, length + 1));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn loop_conclusions(
+        &self,
+        start: usize,
+        end: usize,
+        parents: &BTreeMap<(usize, bool), (usize, bool)>,
+    ) -> Vec<Conclusion> {
+        // 連続ループ: ループ内の弱リンクはすべて強リンクとして扱える
+        let mut weak_links = vec![(end, start)];
+        let mut key = (end, true);
+        while let Some(parent) = parents.get(&key) {
+            if !key.1 {
+                weak_links.push((parent.0, key.0));
+            }
+            key = *parent;
+        }
+
+        weak_links
+            .into_iter()
+            .flat_map(|(a, b)| {
+                self.context
+                    .common_weak_targets(self.graph.node(a), self.graph.node(b))
+            })
+            .collect()
+    }
+}
+
+fn search_chains(
+    event_queue: &mut EventQueue,
+    candidates: &Board<Candidate>,
+    kind: LinkKind,
+) -> bool {
+    let search = ChainSearch {
+        context: ChainContext::new(candidates),
+        graph: LinkGraph::new(candidates, kind),
+    };
+    match search.search() {
+        Some((conclusions, chain)) => {
+            push_conclusions(event_queue, candidates, conclusions, &chain);
+            true
+        }
+        None => false,
+    }
+}
+pub struct XChain;
+
+impl NamedFilter for XChain {
+    fn name(&self) -> &'static str {
+        "XChain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+}
+
+impl FilterCandidates for XChain {
+    fn filter_candidates(&self, input: FilterInput) {
+        for digit in Digit::range_iter(input.candidates.width()) {
+            let kind = LinkKind::SingleDigit(digit);
+            if search_chains(input.event_queue, input.candidates, kind) {
+                break;
+            }
+        }
+    }
+}
+
+pub struct XYChain;
+
+impl NamedFilter for XYChain {
+    fn name(&self) -> &'static str {
+        "XYChain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.8
+    }
+}
+
+impl FilterCandidates for XYChain {
+    fn filter_candidates(&self, input: FilterInput) {
+        search_chains(input.event_queue, input.candidates, LinkKind::Bivalue);
+    }
+}
+
+pub struct AlternatingInferenceChain;
+
+impl NamedFilter for AlternatingInferenceChain {
+    fn name(&self) -> &'static str {
+        "AlternatingInferenceChain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.0
+    }
+}
+
+impl FilterCandidates for AlternatingInferenceChain {
+    fn filter_candidates(&self, input: FilterInput) {
+        search_chains(input.event_queue, input.candidates, LinkKind::All);
+    }
+}
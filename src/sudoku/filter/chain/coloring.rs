@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+use crate::sudoku::digit::Digit;
+use crate::sudoku::filter::chain::graph::{LinkGraph, LinkKind, Node};
+use crate::sudoku::filter::chain::{push_conclusions, ChainContext, Conclusion};
+use crate::sudoku::filter::{FilterCandidates, FilterInput, NamedFilter};
+use crate::sudoku::positions::CellPositions;
+
+pub struct SimpleColoring;
+
+impl SimpleColoring {
+    fn search(&self, context: &ChainContext, digit: Digit) -> Option<(Vec<Conclusion>, Vec<Node>)> {
+        let graph = LinkGraph::new(context.candidates, LinkKind::SingleDigit(digit));
+
+        let mut colors: Vec<Option<bool>> = vec![None; graph.len()];
+        for start in 0..graph.len() {
+            if colors[start].is_some() || graph.strong_links(start).next().is_none() {
+                continue;
+            }
+
+            // 強リンクを辿って2色に塗り分ける
+            let mut cells = [CellPositions::default(); 2];
+            let mut queue = VecDeque::new();
+            colors[start] = Some(false);
+            queue.push_back(start);
+            while let Some(index) = queue.pop_front() {
+                let color = colors[index].unwrap();
+                cells[color as usize].set(graph.node(index).cell);
+                for next in graph.strong_links(index) {
+                    if colors[next].is_none() {
+                        colors[next] = Some(!color);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            // 塗り分けたセルがステップの根拠になる
+            let chain = cells[0]
+                .or(&cells[1])
+                .iter()
+                .map(|cell| Node { cell, digit })
+                .collect::<Vec<_>>();
+
+            // 同じ色同士が同一ユニットにあれば、その色はすべて偽
+            for color_cells in cells.iter() {
+                let wrapped = color_cells
+                    .iter()
+                    .any(|cell| !context.peers[cell].and(color_cells).is_empty());
+                if wrapped {
+                    return context
+                        .remove(digit, *color_cells)
+                        .map(|conclusion| (vec![conclusion], chain));
+                }
+            }
+
+            // 両方の色から見えるセルは偽
+            let trapped = (0..graph.len())
+                .map(|index| graph.node(index).cell)
+                .filter(|cell| !cells[0].contains(*cell) && !cells[1].contains(*cell))
+                .filter(|cell| {
+                    let peers = context.peers[*cell];
+                    !peers.and(&cells[0]).is_empty() && !peers.and(&cells[1]).is_empty()
+                })
+                .collect::<CellPositions>();
+            if let Some(conclusion) = context.remove(digit, trapped) {
+                return Some((vec![conclusion], chain));
+            }
+        }
+
+        None
+    }
+}
+
+impl NamedFilter for SimpleColoring {
+    fn name(&self) -> &'static str {
+        "SimpleColoring"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.6
+    }
+}
+
+impl FilterCandidates for SimpleColoring {
+    fn filter_candidates(&self, input: FilterInput) {
+        let context = ChainContext::new(input.candidates);
+        for digit in Digit::range_iter(input.candidates.width()) {
+            if let Some((conclusions, chain)) = self.search(&context, digit) {
+                push_conclusions(input.event_queue, input.candidates, conclusions, &chain);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use itertools::Itertools;
+
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::Board;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Node {
+    pub cell: usize,
+    pub digit: Digit,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LinkKind {
+    SingleDigit(Digit),
+    Bivalue,
+    All,
+}
+
+impl LinkKind {
+    fn contains(&self, digit: Digit, candidate: &Candidate) -> bool {
+        match *self {
+            LinkKind::SingleDigit(d) => d == digit,
+            LinkKind::Bivalue => candidate.digits().len() == 2,
+            LinkKind::All => true,
+        }
+    }
+}
+
+pub struct LinkGraph {
+    nodes: Vec<Node>,
+    indexes: BTreeMap<Node, usize>,
+    strong: Vec<BTreeSet<usize>>,
+    weak: Vec<BTreeSet<usize>>,
+}
+
+impl LinkGraph {
+    pub fn new(candidates: &Board<Candidate>, kind: LinkKind) -> Self {
+        let nodes = candidates
+            .item_positions()
+            .flat_map(|pos| {
+                let cell = candidates.item_index(pos);
+                let candidate = candidates.item_at(pos);
+                candidate
+                    .digits_iter()
+                    .filter(move |d| kind.contains(*d, candidate))
+                    .map(move |digit| Node { cell, digit })
+            })
+            .collect_vec();
+        let indexes = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (*node, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut graph = LinkGraph {
+            strong: vec![BTreeSet::new(); nodes.len()],
+            weak: vec![BTreeSet::new(); nodes.len()],
+            nodes,
+            indexes,
+        };
+
+        // 同一ユニット内の同じ数字: 2箇所にしかなければ強リンク、それ以外は弱リンク
+        for scope in candidates.scopes() {
+            let cells = candidates
+                .scope_positions(scope)
+                .into_iter()
+                .filter(|pos| candidates.item_at(*pos).has_candidate())
+                .collect_vec();
+            for digit in Digit::range_iter(candidates.width()) {
+                let digit_cells = cells
+                    .iter()
+                    .filter(|pos| candidates.item_at(**pos).contains(digit))
+                    .map(|pos| candidates.item_index(*pos))
+                    .collect_vec();
+                let linked = digit_cells
+                    .iter()
+                    .filter_map(|cell| graph.index_of(Node { cell: *cell, digit }))
+                    .collect_vec();
+
+                let is_conjugate = digit_cells.len() == 2 && kind != LinkKind::Bivalue;
+                for (a, b) in linked.into_iter().tuple_combinations() {
+                    if is_conjugate {
+                        graph.add_strong_link(a, b);
+                    }
+                    graph.add_weak_link(a, b);
+                }
+            }
+        }
+
+        // 同一セル内の数字: 2択なら強リンク、それ以外は弱リンク
+        if let LinkKind::SingleDigit(_) = kind {
+            return graph;
+        }
+        for pos in candidates.item_positions() {
+            let cell = candidates.item_index(pos);
+            let candidate = candidates.item_at(pos);
+            let linked = candidate
+                .digits_iter()
+                .filter_map(|digit| graph.index_of(Node { cell, digit }))
+                .collect_vec();
+
+            for (a, b) in linked.into_iter().tuple_combinations() {
+                if candidate.digits().len() == 2 {
+                    graph.add_strong_link(a, b);
+                }
+                if kind == LinkKind::All {
+                    graph.add_weak_link(a, b);
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn add_strong_link(&mut self, a: usize, b: usize) {
+        self.strong[a].insert(b);
+        self.strong[b].insert(a);
+    }
+
+    fn add_weak_link(&mut self, a: usize, b: usize) {
+        self.weak[a].insert(b);
+        self.weak[b].insert(a);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, index: usize) -> Node {
+        self.nodes[index]
+    }
+
+    pub fn index_of(&self, node: Node) -> Option<usize> {
+        self.indexes.get(&node).copied()
+    }
+
+    pub fn strong_links(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.strong[index].iter().copied()
+    }
+
+    pub fn weak_links(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.weak[index].iter().copied()
+    }
+
+    pub fn is_weakly_linked(&self, a: usize, b: usize) -> bool {
+        self.weak[a].contains(&b)
+    }
+}
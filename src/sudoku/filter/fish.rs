@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+use itertools::Itertools;
+
+use crate::sudoku::action::{ActionScope, RemoveAction};
+use crate::sudoku::board::ItemPosition;
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::Event;
+use crate::sudoku::filter::{FilterContext, FilterInput, NamedFilter, ScanCandidates};
+use crate::sudoku::positions::{ColumnPositions, Positions, RowPositions};
+use crate::sudoku::set::DigitSet;
+
+pub struct Fish {
+    size: usize,
+    finned: bool,
+}
+
+impl Fish {
+    pub fn new(size: usize, finned: bool) -> Self {
+        assert!(size >= 2);
+        Fish { size, finned }
+    }
+
+    pub fn x_wing() -> Self {
+        Self::new(2, false)
+    }
+
+    pub fn swordfish() -> Self {
+        Self::new(3, false)
+    }
+
+    pub fn jellyfish() -> Self {
+        Self::new(4, false)
+    }
+
+    pub fn finned_x_wing() -> Self {
+        Self::new(2, true)
+    }
+
+    pub fn finned_swordfish() -> Self {
+        Self::new(3, true)
+    }
+
+    pub fn finned_jellyfish() -> Self {
+        Self::new(4, true)
+    }
+
+    fn collect_line_positions<'a, F, I>(
+        &self,
+        context: &mut FilterContext,
+        num_lines: usize,
+        iter_fn: F,
+    ) -> BTreeMap<Digit, Vec<Positions>>
+    where
+        F: Fn(usize) -> I,
+        I: Iterator<Item = &'a Candidate>,
+    {
+        // 数字ごとに、各行(列)での配置を調べる
+        let mut line_positions = BTreeMap::new();
+        for line in 0..num_lines {
+            context.collect_digit_positions(iter_fn(line));
+            for (digit, positions) in context.digit_positions.iter() {
+                line_positions
+                    .entry(*digit)
+                    .or_insert_with(|| vec![Positions::default(); num_lines])[line] = *positions;
+            }
+        }
+        line_positions
+    }
+
+    fn search_fish<P, F>(
+        &self,
+        input: &mut FilterInput,
+        digit: Digit,
+        lines: &[Positions],
+        max_fins: usize,
+        position_of: P,
+        scope_fn: F,
+    ) where
+        P: Fn(usize, usize) -> ItemPosition,
+        F: Fn(usize, Positions) -> ActionScope,
+    {
+        let board = input.board;
+        let block_of = |line, i| board.block_position_of(position_of(line, i));
+        // ベースの行(列)のうち、指定した位置にある候補のセル
+        let pattern = |base: &[(usize, &Positions)], cover: &Positions| {
+            base.iter()
+                .flat_map(|(line, ps)| ps.and(cover).iter().map(move |i| (*line, i)))
+                .map(|(line, i)| position_of(line, i))
+                .collect_vec()
+        };
+        let digits = DigitSet::from_iter(Some(digit));
+
+        let num_lines = lines.len();
+        let max_positions = if self.finned {
+            self.size + max_fins
+        } else {
+            self.size
+        };
+
+        // ベースになる行(列)を列挙する
+        let base_lines = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, ps)| ps.num_set() >= 2 && ps.num_set() <= max_positions)
+            .collect_vec();
+
+        for base in base_lines.into_iter().combinations(self.size) {
+            let base_positions = Positions::from_iter(base.iter().map(|(i, _)| *i));
+            let covers = base
+                .iter()
+                .fold(Positions::default(), |covers, (_, ps)| covers.or(ps));
+
+            if covers.num_set() == self.size {
+                // ベースの候補がカバーにすべて収まる → カバーのその他の行(列)から除外する
+                if !self.finned {
+                    let positions = base_positions.invert(num_lines);
+                    let cells = pattern(&base, &covers);
+                    for cover in covers.iter() {
+                        let action = RemoveAction::new(digit, scope_fn(cover, positions));
+                        let event = Event::from(action).with_pattern(cells.clone(), digits);
+                        input.event_queue.push_back(event);
+                    }
+                }
+                continue;
+            }
+
+            if !self.finned || covers.num_set() > self.size + max_fins {
+                continue;
+            }
+
+            for cover in covers.iter().combinations(self.size) {
+                let cover = Positions::from_iter(cover);
+                if base.iter().any(|(_, ps)| ps.and(&cover).num_set() == 0) {
+                    continue;
+                }
+
+                // カバーからはみ出した候補(ヒレ)が同一ブロックに収まるか検証する
+                let outside = cover.invert(num_lines);
+                let fins = base
+                    .iter()
+                    .flat_map(|(line, ps)| ps.and(&outside).iter().map(move |i| (*line, i)))
+                    .collect_vec();
+                let block = block_of(fins[0].0, fins[0].1);
+                if !fins.iter().all(|(line, i)| block_of(*line, *i) == block) {
+                    continue;
+                }
+
+                // ヒレと同じブロック内のカバーからのみ除外する
+                let cells = pattern(&base, &cover.or(&outside));
+                let others = base_positions.invert(num_lines);
+                for cover in cover.iter() {
+                    let positions = Positions::from_iter(
+                        others.iter().filter(|line| block_of(*line, cover) == block),
+                    );
+                    if positions.num_set() == 0 {
+                        continue;
+                    }
+                    let action = RemoveAction::new(digit, scope_fn(cover, positions));
+                    let event = Event::from(action).with_pattern(cells.clone(), digits);
+                    input.event_queue.push_back(event);
+                }
+            }
+        }
+    }
+}
+
+impl NamedFilter for Fish {
+    fn name(&self) -> &'static str {
+        match (self.size, self.finned) {
+            (2, false) => "XWing",
+            (3, false) => "Swordfish",
+            (4, false) => "Jellyfish",
+            (2, true) => "FinnedXWing",
+            (3, true) => "FinnedSwordfish",
+            (4, true) => "FinnedJellyfish",
+            (_, false) => "Fish",
+            (_, true) => "FinnedFish",
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match (self.size, self.finned) {
+            (2, false) => 3.2,
+            (3, false) => 3.8,
+            (4, false) => 5.2,
+            (2, true) => 3.4,
+            (3, true) => 4.0,
+            (4, true) => 5.4,
+            (_, false) => 6.0,
+            (_, true) => 6.2,
+        }
+    }
+}
+
+impl ScanCandidates for Fish {
+    fn scan_rows(&self, input: &mut FilterInput) {
+        let board = input.board;
+        let candidates = input.candidates;
+        let line_positions =
+            self.collect_line_positions(input.context, candidates.height(), |row| {
+                candidates.row_items(row)
+            });
+
+        for (digit, lines) in line_positions {
+            self.search_fish(
+                input,
+                digit,
+                &lines,
+                board.block_width(),
+                |row, col| ItemPosition { row, col },
+                |col, rows| ActionScope::Column(ColumnPositions::new(col, rows)),
+            );
+        }
+    }
+
+    fn scan_columns(&self, input: &mut FilterInput) {
+        let board = input.board;
+        let candidates = input.candidates;
+        let line_positions =
+            self.collect_line_positions(input.context, candidates.width(), |col| {
+                candidates.column_items(col)
+            });
+
+        for (digit, lines) in line_positions {
+            self.search_fish(
+                input,
+                digit,
+                &lines,
+                board.block_height(),
+                |col, row| ItemPosition { row, col },
+                |row, columns| ActionScope::Row(RowPositions::new(row, columns)),
+            );
+        }
+    }
+
+    fn scan_blocks(&self, _input: &mut FilterInput) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::ItemPosition;
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::digit::Digit;
+    use crate::sudoku::filter::tests::{apply_filter, load_candidates};
+    use crate::sudoku::filter::Fish;
+    use crate::sudoku::Board;
+
+    fn contains(candidates: &Board<Candidate>, row: usize, col: usize) -> bool {
+        candidates
+            .item_at(ItemPosition { row, col })
+            .contains(Digit::from(1))
+    }
+
+    #[test]
+    fn test_x_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "023045678",
+            "000000000",
+            "000000000",
+            "056078923",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+        ]);
+
+        // 1 は r1, r4 の c1, c4 にしか入らない
+        assert!(contains(&candidates, 1, 0));
+        assert!(contains(&candidates, 8, 3));
+        assert!(apply_filter(&Fish::x_wing(), &board, &mut candidates));
+
+        for row in (0..9).filter(|&row| row != 0 && row != 3) {
+            assert!(!contains(&candidates, row, 0));
+            assert!(!contains(&candidates, row, 3));
+        }
+        assert!(contains(&candidates, 0, 0));
+        assert!(contains(&candidates, 3, 3));
+    }
+
+    #[test]
+    fn test_finned_x_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "023045678",
+            "000000000",
+            "000000000",
+            "056070923",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+        ]);
+
+        // r4c6 がヒレになるので X-Wing としては成立しない
+        assert!(!apply_filter(&Fish::x_wing(), &board, &mut candidates));
+        assert!(apply_filter(
+            &Fish::finned_x_wing(),
+            &board,
+            &mut candidates
+        ));
+
+        // ヒレと同じブロックの c4 からのみ除外される
+        assert!(!contains(&candidates, 4, 3));
+        assert!(!contains(&candidates, 5, 3));
+        assert!(contains(&candidates, 1, 3));
+        assert!(contains(&candidates, 4, 0));
+    }
+
+    #[test]
+    fn test_swordfish() {
+        let (board, mut candidates) = load_candidates(&[
+            "023045678",
+            "000000000",
+            "000000000",
+            "234056087",
+            "000000000",
+            "000000000",
+            "042367059",
+            "000000000",
+            "000000000",
+        ]);
+
+        // 1 は r1 の c1, c4、r4 の c4, c7、r7 の c1, c7 にしか入らない
+        assert!(!apply_filter(&Fish::x_wing(), &board, &mut candidates));
+        assert!(apply_filter(&Fish::swordfish(), &board, &mut candidates));
+
+        for row in (0..9).filter(|&row| row != 0 && row != 3 && row != 6) {
+            assert!(!contains(&candidates, row, 0));
+            assert!(!contains(&candidates, row, 3));
+            assert!(!contains(&candidates, row, 6));
+            assert!(contains(&candidates, row, 1));
+        }
+        assert!(contains(&candidates, 0, 0));
+        assert!(contains(&candidates, 3, 6));
+        assert!(contains(&candidates, 6, 0));
+    }
+
+    #[test]
+    fn test_jellyfish() {
+        let (board, mut candidates) = load_candidates(&[
+            "023045678",
+            "000000000",
+            "000000000",
+            "234056087",
+            "000000000",
+            "000000000",
+            "342567090",
+            "056238740",
+            "000000000",
+        ]);
+
+        // 1 は r1, r4, r7, r8 の c1, c4, c7, c9 に循環して入る
+        assert!(!apply_filter(&Fish::x_wing(), &board, &mut candidates));
+        assert!(!apply_filter(&Fish::swordfish(), &board, &mut candidates));
+        assert!(apply_filter(&Fish::jellyfish(), &board, &mut candidates));
+
+        for row in [1, 2, 4, 5, 8] {
+            for col in [0, 3, 6, 8] {
+                assert!(!contains(&candidates, row, col));
+            }
+            assert!(contains(&candidates, row, 1));
+        }
+        assert!(contains(&candidates, 0, 3));
+        assert!(contains(&candidates, 7, 8));
+    }
+
+    #[test]
+    fn test_sashimi_x_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "023045678",
+            "000000000",
+            "000000000",
+            "234050769",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+        ]);
+
+        // r4 は c4 と、ヒレの c6 にしか 1 が入らない (r4c1 が欠けた X-Wing)
+        assert!(!contains(&candidates, 3, 0));
+        assert!(!apply_filter(&Fish::x_wing(), &board, &mut candidates));
+        assert!(apply_filter(
+            &Fish::finned_x_wing(),
+            &board,
+            &mut candidates
+        ));
+
+        // ヒレと同じブロックの c4 からのみ除外される
+        assert!(!contains(&candidates, 4, 3));
+        assert!(!contains(&candidates, 5, 3));
+        assert!(contains(&candidates, 1, 3));
+        assert!(contains(&candidates, 4, 0));
+        assert!(contains(&candidates, 4, 5));
+    }
+}
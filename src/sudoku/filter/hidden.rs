@@ -4,7 +4,9 @@ use crate::sudoku::event::{Event, EventQueue};
 use crate::sudoku::filter::{
     FilterCandidates, FilterContext, FilterInput, NamedFilter, ScanCandidates,
 };
-use crate::sudoku::positions::{BlockPositions, ColumnPositions, Positions, RowPositions};
+use crate::sudoku::positions::{
+    BlockPositions, ColumnPositions, Positions, RowPositions, UnitPositions,
+};
 use crate::sudoku::set::DigitSet;
 use itertools::Itertools;
 use std::iter::FromIterator;
@@ -27,13 +29,14 @@ impl HiddenCandidates {
     {
         // 候補を覚えておく
         // - 数字ごとの配置を調べる
-        context.collect_digit_positions_matches(candidates, |_, ps| ps.num_set() >= 2);
+        let candidates = candidates.collect_vec();
+        context
+            .collect_digit_positions_matches(candidates.iter().copied(), |_, ps| ps.num_set() >= 2);
 
         // - 2つ以上のセルで使ってる数値を列挙する
         let hidden_pairs = context
             .digit_positions
-            .iter()
-            .map(|(d, _)| d)
+            .keys()
             .combinations(num_pairs)
             .map(|digits| {
                 // ペアを探す
@@ -68,7 +71,8 @@ impl HiddenCandidates {
             let digits = DigitSet::from_iter(digits.iter().map(|d| **d));
             let scope = scope_fn(positions);
             let action = RetainAction::new(digits, scope);
-            let event = Event::from(action);
+            let cells = positions.items_from_iter(candidates.iter().map(|c| c.position()));
+            let event = Event::from(action).with_pattern(cells, digits);
             event_queue.push_back(event);
         }
 
@@ -124,6 +128,19 @@ impl<T: HiddenCombinations> ScanCandidates for T {
             );
         }
     }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        for unit in input.board.each_units() {
+            HiddenCandidates.search_hidden_digits(
+                input.context,
+                input.event_queue,
+                self.num_combinations(),
+                input.candidates.width(),
+                input.candidates.unit_items(unit),
+                |positions| ActionScope::Unit(UnitPositions::new(unit, positions)),
+            );
+        }
+    }
 }
 
 pub struct HiddenPair;
@@ -132,6 +149,10 @@ impl NamedFilter for HiddenPair {
     fn name(&self) -> &'static str {
         "HiddenPair"
     }
+
+    fn difficulty(&self) -> f32 {
+        3.4
+    }
 }
 
 impl HiddenCombinations for HiddenPair {
@@ -146,6 +167,10 @@ impl NamedFilter for HiddenTriple {
     fn name(&self) -> &'static str {
         "HiddenTriple"
     }
+
+    fn difficulty(&self) -> f32 {
+        4.0
+    }
 }
 
 impl HiddenCombinations for HiddenTriple {
@@ -160,6 +185,10 @@ impl NamedFilter for HiddenQuad {
     fn name(&self) -> &'static str {
         "HiddenQuad"
     }
+
+    fn difficulty(&self) -> f32 {
+        5.4
+    }
 }
 
 impl HiddenCombinations for HiddenQuad {
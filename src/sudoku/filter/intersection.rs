@@ -1,83 +1,193 @@
 use std::iter::FromIterator;
-use std::ops::Range;
+
+use itertools::Itertools;
 
 use crate::sudoku::action::{ActionScope, RemoveAction};
-use crate::sudoku::board::BlockPosition;
-use crate::sudoku::candidate::Candidate;
-use crate::sudoku::event::{Event, EventQueue};
-use crate::sudoku::filter::{FilterContext, FilterInput, NamedFilter, ScanCandidates};
-use crate::sudoku::positions::{BlockPositions, ColumnPositions, Positions, RowPositions};
-
-struct LockedCandidate {
-    block_size: usize,
-    num_blocks: usize,
+use crate::sudoku::board::{ItemPosition, Scope};
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::Event;
+use crate::sudoku::filter::{FilterInput, NamedFilter, ScanCandidates};
+use crate::sudoku::positions::{
+    BlockPositions, ColumnPositions, Positions, RowPositions, UnitPositions,
+};
+use crate::sudoku::set::DigitSet;
+
+fn same_value<T: PartialEq>(mut iter: impl Iterator<Item = T>) -> Option<T> {
+    let first = iter.next()?;
+    if iter.all(|x| x == first) {
+        Some(first)
+    } else {
+        None
+    }
 }
 
-impl LockedCandidate {
-    fn positions_exclude_block(&self, block_offset: usize) -> Positions {
-        let item_start = block_offset * self.block_size;
-        let excludes = item_start..(item_start + self.block_size);
-        let iter = (0..(self.block_size * self.num_blocks)).filter(|i| !excludes.contains(i));
-        Positions::from_iter(iter)
-    }
-
-    fn scan_pointing<'a, FI, I, FS>(
-        &self,
-        context: &mut FilterContext,
-        event_queue: &mut EventQueue,
-        block_offsets: Range<usize>,
-        block_candidates: impl Iterator<Item = &'a Candidate>,
-        iter_fn: FI,
-        scope_fn: FS,
-    ) where
-        FI: Fn(usize) -> I,
-        I: Iterator<Item = &'a Candidate>,
-        FS: Fn(usize) -> ActionScope,
-    {
-        context.collect_digit_positions_matches(block_candidates, |_, positions| {
-            positions.num_set() >= 2 || positions.num_set() <= self.block_size
-        });
-
-        for (digit, positions) in context.digit_positions.iter() {
-            for offset in block_offsets.clone() {
-                let num_digits = iter_fn(offset).filter(|c| c.contains(*digit)).count();
-                if num_digits == positions.num_set() {
-                    // ブロックのどちらかに入るので、その他のブロックの列・行から除外する
-                    let scope = scope_fn(offset);
-                    let action = RemoveAction::new(*digit, scope);
-                    let event = Event::from(action);
-                    event_queue.push_back(event);
+// ブロックと交差させる行または列
+#[derive(Copy, Clone)]
+enum Line {
+    Row,
+    Column,
+}
+
+impl Line {
+    fn index_of(self, pos: ItemPosition) -> usize {
+        match self {
+            Line::Row => pos.row,
+            Line::Column => pos.col,
+        }
+    }
+
+    fn position(self, line: usize, i: usize) -> ItemPosition {
+        match self {
+            Line::Row => ItemPosition { row: line, col: i },
+            Line::Column => ItemPosition { row: i, col: line },
+        }
+    }
+
+    fn scope(self, line: usize, positions: Positions) -> ActionScope {
+        match self {
+            Line::Row => ActionScope::Row(RowPositions::new(line, positions)),
+            Line::Column => ActionScope::Column(ColumnPositions::new(line, positions)),
+        }
+    }
+
+    fn scan_pointing(self, input: &mut FilterInput) {
+        let board = input.board;
+        for block_pos in board.block_positions() {
+            let cells = board.scope_positions(Scope::BoardBlock(block_pos));
+            input
+                .context
+                .collect_digit_positions(input.candidates.block_at(block_pos));
+
+            for (digit, positions) in input.context.digit_positions.iter() {
+                // ブロック内の候補が1つの行(列)に収まる → その行(列)のブロック外から除外する
+                let line = match same_value(positions.iter().map(|i| self.index_of(cells[i]))) {
+                    Some(line) => line,
+                    None => continue,
+                };
+
+                let outside = (0..board.width())
+                    .filter(|i| board.block_position_of(self.position(line, *i)) != block_pos);
+                let scope = self.scope(line, Positions::from_iter(outside));
+                let event = Event::from(RemoveAction::new(*digit, scope)).with_pattern(
+                    positions.iter().map(|i| cells[i]),
+                    DigitSet::from_iter(Some(*digit)),
+                );
+                input.event_queue.push_back(event);
+            }
+        }
+    }
+
+    fn scan_claiming(self, input: &mut FilterInput) {
+        let board = input.board;
+        for line in 0..board.width() {
+            match self {
+                Line::Row => input
+                    .context
+                    .collect_digit_positions(input.candidates.row_items(line)),
+                Line::Column => input
+                    .context
+                    .collect_digit_positions(input.candidates.column_items(line)),
+            }
+
+            for (digit, positions) in input.context.digit_positions.iter() {
+                if positions.num_set() < 2 {
+                    continue;
                 }
+
+                // 行(列)の候補が1つのブロックに収まる → そのブロックの他の行(列)から除外する
+                let block_pos = match same_value(
+                    positions
+                        .iter()
+                        .map(|i| board.block_position_of(self.position(line, i))),
+                ) {
+                    Some(block_pos) => block_pos,
+                    None => continue,
+                };
+
+                let cells = board.scope_positions(Scope::BoardBlock(block_pos));
+                let others = cells.iter().positions(|pos| self.index_of(*pos) != line);
+                let scope = ActionScope::Block(BlockPositions::new(
+                    block_pos,
+                    Positions::from_iter(others),
+                ));
+                let event = Event::from(RemoveAction::new(*digit, scope)).with_pattern(
+                    positions.iter().map(|i| self.position(line, i)),
+                    DigitSet::from_iter(Some(*digit)),
+                );
+                input.event_queue.push_back(event);
             }
         }
     }
+}
+
+fn action_scope(scope: Scope, positions: Positions) -> ActionScope {
+    match scope {
+        Scope::Row(row) => ActionScope::Row(RowPositions::new(row, positions)),
+        Scope::Column(col) => ActionScope::Column(ColumnPositions::new(col, positions)),
+        Scope::BoardBlock(pos) => ActionScope::Block(BlockPositions::new(pos, positions)),
+        Scope::Unit(unit) => ActionScope::Unit(UnitPositions::new(unit, positions)),
+        Scope::Cage(_) => unreachable!("cages are not scanned for intersections"),
+    }
+}
+
+// ユニットと他の家(行・列・ブロック・ユニット)の交差部分を調べる
+// from_unit ならユニットの候補が他の家に収まるか、そうでなければ他の家の候補がユニットに収まるか
+fn scan_unit_intersections(input: &mut FilterInput, from_unit: bool) {
+    let board = input.board;
+    let candidates = input.candidates;
+    let cells_of = |scope| {
+        board
+            .scope_positions(scope)
+            .into_iter()
+            .map(|pos| board.item_index(pos))
+            .collect_vec()
+    };
+    let contains = |cell: usize, digit| {
+        candidates
+            .item_at(board.item_position(cell))
+            .contains(digit)
+    };
+
+    for unit in board.each_units() {
+        let unit_scope = Scope::Unit(unit);
+        let unit_cells = cells_of(unit_scope);
+        for scope in board.scopes().filter(|scope| *scope != unit_scope) {
+            let cells = cells_of(scope);
+            if !cells.iter().any(|cell| unit_cells.contains(cell)) {
+                continue;
+            }
+
+            let (source, target, target_scope) = if from_unit {
+                (&unit_cells, &cells, scope)
+            } else {
+                (&cells, &unit_cells, unit_scope)
+            };
+            for digit in Digit::range_iter(board.width()) {
+                let in_source = source
+                    .iter()
+                    .filter(|cell| contains(**cell, digit))
+                    .collect_vec();
+                if in_source.len() < 2 || !in_source.iter().all(|cell| target.contains(cell)) {
+                    continue;
+                }
 
-    fn scan_claiming<'a, F>(
-        &self,
-        context: &mut FilterContext,
-        event_queue: &mut EventQueue,
-        block_offsets: Range<usize>,
-        candidates: impl Iterator<Item = &'a Candidate>,
-        scope_fn: F,
-    ) where
-        F: Fn(usize) -> ActionScope,
-    {
-        context.collect_digit_positions_matches(candidates, |_, positions| {
-            positions.num_set() >= 2 && positions.num_set() <= self.block_size
-        });
-
-        for (digit, positions) in context.digit_positions.iter() {
-            // 同一のブロック内に収まっている場合は、その他のブロックの候補から削除する
-            for offset in block_offsets.clone() {
-                let item_start = self.block_size * offset;
-                let block_positions = Positions::with_offset(item_start, self.block_size);
-                if positions.belongs_to(&block_positions) {
-                    // すべての候補が同一ブロックに存在する → 同ブロックのその他の行・列から削除する
-                    let scope = scope_fn(offset);
-                    let action = RemoveAction::new(*digit, scope);
-                    let event = Event::from(action);
-                    event_queue.push_back(event);
+                // 交差部分の外にある同じ数字を除外する
+                let outside = target
+                    .iter()
+                    .positions(|cell| !source.contains(cell) && contains(*cell, digit));
+                let positions = Positions::from_iter(outside);
+                if positions.num_set() == 0 {
+                    continue;
                 }
+                let event = Event::from(RemoveAction::new(
+                    digit,
+                    action_scope(target_scope, positions),
+                ))
+                .with_pattern(
+                    in_source.iter().map(|cell| board.item_position(**cell)),
+                    DigitSet::from_iter(Some(digit)),
+                );
+                input.event_queue.push_back(event);
             }
         }
     }
@@ -89,58 +199,26 @@ impl NamedFilter for LockedCandidatePointing {
     fn name(&self) -> &'static str {
         "LockedCandidate(Pointing)"
     }
+
+    fn difficulty(&self) -> f32 {
+        2.6
+    }
 }
 
 impl ScanCandidates for LockedCandidatePointing {
     fn scan_rows(&self, input: &mut FilterInput) {
-        let block_size = input.board.block_size();
-        let locked_candidate = LockedCandidate {
-            block_size,
-            num_blocks: input.board.num_blocks(),
-        };
-
-        for block_pos in input.board.block_positions() {
-            let candidates = input.candidates;
-            locked_candidate.scan_pointing(
-                input.context,
-                input.event_queue,
-                input.board.each_block_rows(),
-                input.candidates.block_at(block_pos),
-                |block_row| candidates.block_at(block_pos).row_items(block_row),
-                |block_row| {
-                    let row = block_pos.row * block_size + block_row;
-                    let positions = locked_candidate.positions_exclude_block(block_pos.col);
-                    ActionScope::Row(RowPositions::new(row, positions))
-                },
-            );
-        }
+        Line::Row.scan_pointing(input);
     }
 
     fn scan_columns(&self, input: &mut FilterInput) {
-        let block_size = input.board.block_size();
-        let locked_candidate = LockedCandidate {
-            block_size,
-            num_blocks: input.board.num_blocks(),
-        };
-
-        for block_pos in input.board.block_positions() {
-            let candidates = input.candidates;
-            locked_candidate.scan_pointing(
-                input.context,
-                input.event_queue,
-                input.board.each_block_columns(),
-                input.candidates.block_at(block_pos),
-                |block_col| candidates.block_at(block_pos).column_items(block_col),
-                |block_col| {
-                    let col = block_pos.col * block_size + block_col;
-                    let positions = locked_candidate.positions_exclude_block(block_pos.row);
-                    ActionScope::Column(ColumnPositions::new(col, positions))
-                },
-            );
-        }
+        Line::Column.scan_pointing(input);
     }
 
     fn scan_blocks(&self, _input: &mut FilterInput) {}
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        scan_unit_intersections(input, true);
+    }
 }
 
 pub struct LockedCandidateClaiming;
@@ -149,69 +227,24 @@ impl NamedFilter for LockedCandidateClaiming {
     fn name(&self) -> &'static str {
         "LockedCandidate(Claiming)"
     }
+
+    fn difficulty(&self) -> f32 {
+        2.8
+    }
 }
 
 impl ScanCandidates for LockedCandidateClaiming {
     fn scan_rows(&self, input: &mut FilterInput) {
-        let block_size = input.board.block_size();
-        let num_blocks = input.board.num_blocks();
-        let locked_candidate = LockedCandidate {
-            block_size,
-            num_blocks,
-        };
-
-        for row in input.board.each_rows() {
-            locked_candidate.scan_claiming(
-                input.context,
-                input.event_queue,
-                input.board.each_block_columns(),
-                input.candidates.row_items(row),
-                |block_col| {
-                    let block_row = row / block_size;
-                    let block_at = BlockPosition {
-                        row: block_row,
-                        col: block_col,
-                    };
-                    let positions =
-                        Positions::with_offset((row % block_size) * block_size, block_size)
-                            .invert(block_size * num_blocks);
-                    ActionScope::Block(BlockPositions::new(block_at, positions))
-                },
-            );
-        }
+        Line::Row.scan_claiming(input);
     }
 
     fn scan_columns(&self, input: &mut FilterInput) {
-        let block_size = input.board.block_size();
-        let num_blocks = input.board.num_blocks();
-        let locked_candidate = LockedCandidate {
-            block_size,
-            num_blocks,
-        };
-
-        for col in input.board.each_columns() {
-            let block_item_indexes = input.board.block_item_indexes();
-            locked_candidate.scan_claiming(
-                input.context,
-                input.event_queue,
-                input.board.each_block_rows(),
-                input.candidates.column_items(col),
-                |block_row| {
-                    let block_col = col / block_size;
-                    let block_at = BlockPosition {
-                        row: block_row,
-                        col: block_col,
-                    };
-                    let iter = block_item_indexes
-                        .clone()
-                        .skip(col % block_size)
-                        .step_by(block_size);
-                    let positions = Positions::from_iter(iter).invert(block_size * num_blocks);
-                    ActionScope::Block(BlockPositions::new(block_at, positions))
-                },
-            );
-        }
+        Line::Column.scan_claiming(input);
     }
 
     fn scan_blocks(&self, _input: &mut FilterInput) {}
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        scan_unit_intersections(input, false);
+    }
 }
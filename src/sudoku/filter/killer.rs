@@ -0,0 +1,351 @@
+use std::iter::FromIterator;
+
+use itertools::Itertools;
+
+use crate::sudoku::action::{ActionScope, RetainAction};
+use crate::sudoku::board::Scope;
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::event::Event;
+use crate::sudoku::filter::{FilterCandidates, FilterInput, NamedFilter};
+use crate::sudoku::positions::CellPositions;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::{Board, Square};
+
+// 合計から割り出すセルの上限、これより多いと組み合わせの列挙が重くなる
+const MAX_SUM_CELLS: usize = 4;
+
+fn cell_digits(board: &Board<Square>, candidates: &Board<Candidate>, cell: usize) -> DigitSet {
+    let pos = board.item_position(cell);
+    match board.item_at(pos).digit() {
+        Some(d) => DigitSet::from_iter(Some(d)),
+        None => candidates.item_at(pos).digits(),
+    }
+}
+
+fn can_assign(cells: &[DigitSet], digits: DigitSet) -> bool {
+    match cells.split_first() {
+        None => true,
+        Some((first, rest)) => (*first & digits).iter().any(|d| {
+            let mut digits = digits;
+            digits.remove(d);
+            can_assign(rest, digits)
+        }),
+    }
+}
+
+// 重複なしで合計が sum になる割り当てのうち、各セルに実際に置ける数字を求める
+pub(crate) fn possible_digits(cells: &[DigitSet], sum: u32) -> Vec<DigitSet> {
+    let union = cells.iter().fold(DigitSet::default(), |u, ds| u | *ds);
+    let mut possible = vec![DigitSet::default(); cells.len()];
+
+    for combination in union.iter().combinations(cells.len()) {
+        if combination.iter().map(|d| d.get() as u32).sum::<u32>() != sum {
+            continue;
+        }
+
+        let combination = DigitSet::from_iter(combination);
+        for (i, digits) in cells.iter().enumerate() {
+            let others = cells
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, ds)| *ds)
+                .collect_vec();
+            for d in *digits & combination {
+                let mut rest = combination;
+                rest.remove(d);
+                if can_assign(&others, rest) {
+                    possible[i].set(d);
+                }
+            }
+        }
+    }
+    possible
+}
+
+fn push_retain_events(input: &mut FilterInput, cells: &[usize], sum: u32) {
+    let digits = cells
+        .iter()
+        .map(|cell| cell_digits(input.board, input.candidates, *cell))
+        .collect_vec();
+    let possible = possible_digits(&digits, sum);
+    // ケージ (または領域) のセル全体と、そこに置ける数字が根拠になる
+    let pattern = cells
+        .iter()
+        .map(|cell| input.board.item_position(*cell))
+        .collect_vec();
+    let pattern_digits = possible
+        .iter()
+        .fold(DigitSet::default(), |digits, ds| digits | *ds);
+
+    for ((cell, before), after) in cells.iter().zip(digits).zip(possible) {
+        let pos = input.board.item_position(*cell);
+        if input.board.item_at(pos).is_fixed() || before == after {
+            continue;
+        }
+
+        let scope = ActionScope::Cells(CellPositions::from_iter(Some(*cell)));
+        let event = Event::from(RetainAction::new(after, scope))
+            .with_pattern(pattern.clone(), pattern_digits);
+        input.event_queue.push_back(event);
+    }
+}
+
+pub struct CageCombination;
+
+impl NamedFilter for CageCombination {
+    fn name(&self) -> &'static str {
+        "CageCombination"
+    }
+
+    fn difficulty(&self) -> f32 {
+        1.8
+    }
+}
+
+impl FilterCandidates for CageCombination {
+    fn filter_candidates(&self, mut input: FilterInput) {
+        for cage in input.board.each_cages() {
+            let cage = input.board.cages()[cage].clone();
+            push_retain_events(&mut input, cage.cells(), cage.sum());
+        }
+    }
+}
+
+pub struct RuleOf45;
+
+impl RuleOf45 {
+    fn regions(&self, board: &Board<Square>) -> Vec<(usize, Vec<usize>)> {
+        // 連続する k 行(列)と各ブロック、含まれる家の数とセルの組
+        let size = board.width();
+        let mut regions = Vec::new();
+        for k in 1..size {
+            for start in 0..=(size - k) {
+                let rows = (start..start + k)
+                    .flat_map(|row| board.each_columns().map(move |col| row * size + col))
+                    .sorted()
+                    .collect();
+                regions.push((k, rows));
+
+                let columns = (start..start + k)
+                    .flat_map(|col| board.each_rows().map(move |row| row * size + col))
+                    .sorted()
+                    .collect();
+                regions.push((k, columns));
+            }
+        }
+        for block_pos in board.block_positions() {
+            let cells = board
+                .scope_positions(Scope::BoardBlock(block_pos))
+                .into_iter()
+                .map(|pos| board.item_index(pos))
+                .sorted()
+                .collect();
+            regions.push((1, cells));
+        }
+        regions
+    }
+
+    fn apply_sum(&self, input: &mut FilterInput, cells: Vec<usize>, sum: u32) {
+        // 確定済みのセルは合計から差し引く
+        let mut sum = sum as i64;
+        let mut rest = Vec::new();
+        for cell in cells {
+            match input.board.item_at(input.board.item_position(cell)).digit() {
+                Some(d) => sum -= d.get() as i64,
+                None => rest.push(cell),
+            }
+        }
+        if rest.is_empty() || rest.len() > MAX_SUM_CELLS || sum <= 0 {
+            return;
+        }
+
+        // 互いに同じ数字を置けないセルの組だけを対象にする
+        let distinct = rest.iter().tuple_combinations().all(|(a, b)| {
+            input
+                .board
+                .peer_positions(input.board.item_position(*a))
+                .contains(*b)
+        });
+        if distinct {
+            push_retain_events(input, &rest, sum as u32);
+        }
+    }
+}
+
+impl NamedFilter for RuleOf45 {
+    fn name(&self) -> &'static str {
+        "RuleOf45"
+    }
+
+    fn difficulty(&self) -> f32 {
+        2.2
+    }
+}
+
+impl FilterCandidates for RuleOf45 {
+    fn filter_candidates(&self, mut input: FilterInput) {
+        if input.board.cages().is_empty() {
+            return;
+        }
+
+        let size = input.board.width() as u32;
+        let house_sum = size * (size + 1) / 2;
+        for (k, region) in self.regions(input.board) {
+            let cages = input.board.cages();
+            let in_region = CellPositions::from_iter(region.iter().copied());
+            let total = k as u32 * house_sum;
+
+            // 領域に完全に含まれるケージ以外のセルが innie
+            let (inner, outer): (Vec<_>, Vec<_>) = cages
+                .iter()
+                .filter(|cage| cage.cells().iter().any(|c| in_region.contains(*c)))
+                .partition(|cage| cage.cells().iter().all(|c| in_region.contains(*c)));
+            let covered = inner
+                .iter()
+                .flat_map(|cage| cage.cells().iter().copied())
+                .collect::<CellPositions>();
+            let inner_sum = inner.iter().map(|cage| cage.sum()).sum::<u32>();
+            let innies = region
+                .iter()
+                .copied()
+                .filter(|c| !covered.contains(*c))
+                .collect_vec();
+
+            // 領域がすべてケージに覆われていれば、はみ出したセルが outie
+            let all_covered = outer
+                .iter()
+                .flat_map(|cage| cage.cells().iter().copied())
+                .collect::<CellPositions>()
+                .or(&covered);
+            let outies = if region.iter().all(|c| all_covered.contains(*c)) {
+                let outer_sum = outer.iter().map(|cage| cage.sum()).sum::<u32>();
+                let cells = outer
+                    .iter()
+                    .flat_map(|cage| cage.cells().iter().copied())
+                    .filter(|c| !in_region.contains(*c))
+                    .collect_vec();
+                (inner_sum + outer_sum)
+                    .checked_sub(total)
+                    .map(|sum| (cells, sum))
+            } else {
+                None
+            };
+
+            if !innies.is_empty() {
+                if let Some(sum) = total.checked_sub(inner_sum) {
+                    self.apply_sum(&mut input, innies, sum);
+                }
+            }
+            if let Some((cells, sum)) = outies {
+                self.apply_sum(&mut input, cells, sum);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::ItemPosition;
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::digit::Digit;
+    use crate::sudoku::filter::killer::possible_digits;
+    use crate::sudoku::filter::tests::{apply_filter, load_candidates};
+    use crate::sudoku::filter::{CageCombination, NakedSingle, RuleOf45};
+    use crate::sudoku::set::DigitSet;
+    use crate::sudoku::Board;
+
+    fn digits(ds: &[u8]) -> DigitSet {
+        ds.iter().map(|d| Digit::from(*d)).collect()
+    }
+
+    fn digits_at(candidates: &Board<Candidate>, row: usize, col: usize) -> Vec<u8> {
+        candidates
+            .item_at(ItemPosition { row, col })
+            .digits_iter()
+            .map(|d| d.get())
+            .collect()
+    }
+
+    #[test]
+    fn test_possible_digits() {
+        let all = digits(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(
+            vec![digits(&[1, 2]), digits(&[1, 2])],
+            possible_digits(&[all, all], 3)
+        );
+        assert_eq!(
+            vec![digits(&[8, 9]), digits(&[8, 9])],
+            possible_digits(&[all, all], 17)
+        );
+
+        // 片方が 5 に決まっていれば、もう片方は 4 しかない
+        assert_eq!(
+            vec![digits(&[5]), digits(&[4])],
+            possible_digits(&[digits(&[5]), all], 9)
+        );
+        assert_eq!(
+            vec![DigitSet::default(); 2],
+            possible_digits(&[all, all], 2)
+        );
+    }
+
+    #[test]
+    fn test_cage_combination() {
+        let (mut board, _) = load_candidates(&["000000000"; 9]);
+        board.add_cage(
+            3,
+            vec![
+                ItemPosition { row: 0, col: 0 },
+                ItemPosition { row: 0, col: 1 },
+            ],
+        );
+        board.add_cage(
+            24,
+            (0..3).map(|row| ItemPosition {
+                row: row + 3,
+                col: 8,
+            }),
+        );
+        let items = board
+            .items()
+            .map(|sq| Candidate::new(sq, board.width()))
+            .collect();
+        let mut candidates = board.with_items(items);
+        assert!(apply_filter(&CageCombination, &board, &mut candidates));
+
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 0));
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 1));
+        assert_eq!(vec![7, 8, 9], digits_at(&candidates, 4, 8));
+        assert_eq!(9, digits_at(&candidates, 0, 2).len());
+    }
+
+    #[test]
+    fn test_rule_of_45() {
+        let (mut board, _) = load_candidates(&["000000000"; 9]);
+
+        // 1行目を r1c1-c8 のケージが覆うと、r1c9 は 45 - 38 = 7 になる
+        board.add_cage(38, (0..8).map(|col| ItemPosition { row: 0, col }));
+        // 2行目は r2c2-c9 と、はみ出した r3c1 を含むケージで覆う
+        board.add_cage(40, (1..9).map(|col| ItemPosition { row: 1, col }));
+        board.add_cage(
+            11,
+            vec![
+                ItemPosition { row: 1, col: 0 },
+                ItemPosition { row: 2, col: 0 },
+            ],
+        );
+        let items = board
+            .items()
+            .map(|sq| Candidate::new(sq, board.width()))
+            .collect();
+        let mut candidates = board.with_items(items);
+        apply_filter(&NakedSingle, &board, &mut candidates);
+        assert!(apply_filter(&RuleOf45, &board, &mut candidates));
+
+        assert_eq!(vec![7], digits_at(&candidates, 0, 8));
+        // r2c1 = 45 - 40 = 5 なので outie の r3c1 は 11 - 5 = 6 になる
+        assert_eq!(vec![5], digits_at(&candidates, 1, 0));
+        assert_eq!(vec![6], digits_at(&candidates, 2, 0));
+    }
+}
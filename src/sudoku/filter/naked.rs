@@ -0,0 +1,312 @@
+use std::iter::FromIterator;
+
+use itertools::Itertools;
+
+use crate::sudoku::action::{ActionScope, RemoveAction};
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::event::{Event, EventQueue};
+use crate::sudoku::filter::{FilterInput, NamedFilter, ScanCandidates};
+use crate::sudoku::positions::{
+    BlockPositions, ColumnPositions, Positions, RowPositions, UnitPositions,
+};
+use crate::sudoku::set::DigitSet;
+
+struct NakedCandidates {
+    num_combinations: usize,
+}
+
+impl NakedCandidates {
+    fn new(num_combinations: usize) -> Self {
+        NakedCandidates { num_combinations }
+    }
+
+    fn search_naked_digits<'a, I, F>(
+        &self,
+        event_queue: &mut EventQueue,
+        num_candidates: usize,
+        candidates: I,
+        scope_fn: F,
+    ) where
+        I: Iterator<Item = &'a Candidate>,
+        F: Fn(Positions) -> ActionScope,
+    {
+        // 候補数が2以上、組の数以下のセルを列挙する
+        let cells = candidates
+            .enumerate()
+            .map(|(i, c)| (i, c.digits(), c.position()))
+            .filter(|(_, digits, _)| digits.len() >= 2 && digits.len() <= self.num_combinations)
+            .collect::<Vec<_>>();
+
+        for cells in cells.iter().combinations(self.num_combinations) {
+            // 組を構成するセルの候補の和集合が、組の数と一致するか検証する
+            let digits = cells
+                .iter()
+                .fold(DigitSet::default(), |digits, (_, ds, _)| digits | *ds);
+            if digits.len() != self.num_combinations {
+                continue;
+            }
+
+            // 組以外のセルから組を構成する数字を削除する
+            let positions =
+                Positions::from_iter(cells.iter().map(|(i, _, _)| *i)).invert(num_candidates);
+            for d in digits {
+                let action = RemoveAction::new(d, scope_fn(positions));
+                let event = Event::from(action).with_pattern(cells.iter().map(|c| c.2), digits);
+                event_queue.push_back(event);
+            }
+        }
+    }
+
+    fn scan_rows(&self, input: &mut FilterInput) {
+        for row in input.board.each_rows() {
+            self.search_naked_digits(
+                input.event_queue,
+                input.candidates.width(),
+                input.candidates.row_items(row),
+                |positions| ActionScope::Row(RowPositions::new(row, positions)),
+            );
+        }
+    }
+
+    fn scan_columns(&self, input: &mut FilterInput) {
+        for col in input.board.each_columns() {
+            self.search_naked_digits(
+                input.event_queue,
+                input.candidates.height(),
+                input.candidates.column_items(col),
+                |positions| ActionScope::Column(ColumnPositions::new(col, positions)),
+            );
+        }
+    }
+
+    fn scan_blocks(&self, input: &mut FilterInput) {
+        for block_pos in input.board.block_positions() {
+            self.search_naked_digits(
+                input.event_queue,
+                input.candidates.block_item_indexes().len(),
+                input.candidates.block_at(block_pos),
+                |positions| ActionScope::Block(BlockPositions::new(block_pos, positions)),
+            );
+        }
+    }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        for unit in input.board.each_units() {
+            self.search_naked_digits(
+                input.event_queue,
+                input.candidates.width(),
+                input.candidates.unit_items(unit),
+                |positions| ActionScope::Unit(UnitPositions::new(unit, positions)),
+            );
+        }
+    }
+}
+
+pub struct NakedPair;
+
+impl NamedFilter for NakedPair {
+    fn name(&self) -> &'static str {
+        "NakedPair"
+    }
+
+    fn difficulty(&self) -> f32 {
+        3.0
+    }
+}
+
+impl ScanCandidates for NakedPair {
+    fn scan_rows(&self, input: &mut FilterInput) {
+        NakedCandidates::new(2).scan_rows(input);
+    }
+
+    fn scan_columns(&self, input: &mut FilterInput) {
+        NakedCandidates::new(2).scan_columns(input);
+    }
+
+    fn scan_blocks(&self, input: &mut FilterInput) {
+        NakedCandidates::new(2).scan_blocks(input);
+    }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        NakedCandidates::new(2).scan_units(input);
+    }
+}
+
+pub struct NakedTriple;
+
+impl NamedFilter for NakedTriple {
+    fn name(&self) -> &'static str {
+        "NakedTriple"
+    }
+
+    fn difficulty(&self) -> f32 {
+        3.6
+    }
+}
+
+impl ScanCandidates for NakedTriple {
+    fn scan_rows(&self, input: &mut FilterInput) {
+        NakedCandidates::new(3).scan_rows(input);
+    }
+
+    fn scan_columns(&self, input: &mut FilterInput) {
+        NakedCandidates::new(3).scan_columns(input);
+    }
+
+    fn scan_blocks(&self, input: &mut FilterInput) {
+        NakedCandidates::new(3).scan_blocks(input);
+    }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        NakedCandidates::new(3).scan_units(input);
+    }
+}
+
+pub struct NakedQuad;
+
+impl NamedFilter for NakedQuad {
+    fn name(&self) -> &'static str {
+        "NakedQuad"
+    }
+
+    fn difficulty(&self) -> f32 {
+        5.0
+    }
+}
+
+impl ScanCandidates for NakedQuad {
+    fn scan_rows(&self, input: &mut FilterInput) {
+        NakedCandidates::new(4).scan_rows(input);
+    }
+
+    fn scan_columns(&self, input: &mut FilterInput) {
+        NakedCandidates::new(4).scan_columns(input);
+    }
+
+    fn scan_blocks(&self, input: &mut FilterInput) {
+        NakedCandidates::new(4).scan_blocks(input);
+    }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        NakedCandidates::new(4).scan_units(input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::{Board, ItemPosition};
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::digit::Digit;
+    use crate::sudoku::filter::tests::{apply_filter, load_candidates};
+    use crate::sudoku::filter::{NakedPair, NakedQuad, NakedTriple};
+    use crate::sudoku::set::DigitSet;
+
+    const EMPTY: [&str; 9] = ["000000000"; 9];
+
+    fn digits(ds: &[u8]) -> DigitSet {
+        ds.iter().map(|d| Digit::from(*d)).collect()
+    }
+
+    // 空の盤面の1行目に、指定したセルの候補を置く
+    fn set_row(candidates: &mut Board<Candidate>, cells: &[(usize, &[u8])]) {
+        for (col, ds) in cells {
+            let keep = digits(ds);
+            let candidate = candidates.item_at_mut(ItemPosition { row: 0, col: *col });
+            let others = candidate.digits_vec();
+            candidate.remove_iter(others.into_iter().filter(|d| !keep.contains(*d)));
+        }
+    }
+
+    fn row_digits(candidates: &Board<Candidate>) -> Vec<DigitSet> {
+        (0..9)
+            .map(|col| candidates.item_at(ItemPosition { row: 0, col }).digits())
+            .collect()
+    }
+
+    #[test]
+    fn test_naked_pair() {
+        let (board, mut candidates) = load_candidates(&[
+            "123450000",
+            "000006000",
+            "000007000",
+            "000000600",
+            "000000700",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+        ]);
+
+        // r1c6, r1c7 が {8, 9} のペアになる
+        assert_eq!(
+            vec![Digit::from(8), Digit::from(9)],
+            candidates
+                .item_at(ItemPosition { row: 0, col: 5 })
+                .digits_vec()
+        );
+        assert!(apply_filter(&NakedPair, &board, &mut candidates));
+
+        for col in 7..9 {
+            assert_eq!(
+                vec![Digit::from(6), Digit::from(7)],
+                candidates
+                    .item_at(ItemPosition { row: 0, col })
+                    .digits_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn test_naked_triple() {
+        // どのセルも3つの数字を全部は持たない {1,2}, {2,3}, {1,3} の組
+        let (board, mut candidates) = load_candidates(&EMPTY);
+        set_row(&mut candidates, &[(0, &[1, 2]), (4, &[2, 3]), (8, &[1, 3])]);
+        assert!(!apply_filter(&NakedPair, &board, &mut candidates.clone()));
+        assert!(apply_filter(&NakedTriple, &board, &mut candidates));
+
+        // 組以外のセルから 1, 2, 3 が除外され、組のセルはそのまま
+        let row = row_digits(&candidates);
+        assert_eq!(digits(&[1, 2]), row[0]);
+        assert_eq!(digits(&[2, 3]), row[4]);
+        assert_eq!(digits(&[1, 3]), row[8]);
+        for col in [1, 2, 3, 5, 6, 7].iter() {
+            assert_eq!(digits(&[4, 5, 6, 7, 8, 9]), row[*col]);
+        }
+
+        // 他の行は変わらない
+        assert_eq!(
+            9,
+            candidates
+                .item_at(ItemPosition { row: 1, col: 0 })
+                .digits()
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_naked_quad() {
+        let (board, mut candidates) = load_candidates(&EMPTY);
+        set_row(
+            &mut candidates,
+            &[(0, &[1, 2]), (2, &[2, 3]), (4, &[3, 4]), (8, &[1, 4])],
+        );
+        assert!(!apply_filter(&NakedTriple, &board, &mut candidates.clone()));
+        assert!(apply_filter(&NakedQuad, &board, &mut candidates));
+
+        let row = row_digits(&candidates);
+        assert_eq!(digits(&[1, 2]), row[0]);
+        assert_eq!(digits(&[2, 3]), row[2]);
+        assert_eq!(digits(&[3, 4]), row[4]);
+        assert_eq!(digits(&[1, 4]), row[8]);
+        for col in [1, 3, 5, 6, 7].iter() {
+            assert_eq!(digits(&[5, 6, 7, 8, 9]), row[*col]);
+        }
+        assert_eq!(
+            9,
+            candidates
+                .item_at(ItemPosition { row: 1, col: 0 })
+                .digits()
+                .len()
+        );
+    }
+}
@@ -4,7 +4,10 @@ use crate::sudoku::action::{ActionScope, RemoveAction, RetainAction};
 use crate::sudoku::candidate::Candidate;
 use crate::sudoku::event::{Event, EventQueue};
 use crate::sudoku::filter::{FilterContext, FilterInput, NamedFilter, ScanCandidates};
-use crate::sudoku::positions::{BlockPositions, ColumnPositions, Positions, RowPositions};
+use crate::sudoku::positions::{
+    BlockPositions, ColumnPositions, Positions, RowPositions, UnitPositions,
+};
+use crate::sudoku::set::DigitSet;
 use crate::sudoku::Square;
 
 pub struct NakedSingle;
@@ -16,15 +19,13 @@ impl NakedSingle {
         scope: ActionScope,
         squares: impl Iterator<Item = &'a Square>,
     ) {
-        input.context.digits_in_use.clear();
-        input
-            .context
-            .digits_in_use
-            .extend(squares.filter_map(|sq| sq.digit()));
-
-        for d in input.context.digits_in_use.iter() {
-            let event = Event::from(RemoveAction::new(d, scope));
-            input.event_queue.push_back(event);
+        // 確定したセルを根拠に、同じ家の他のセルから数字を除外する
+        for sq in squares {
+            if let Some(d) = sq.digit() {
+                let event = Event::from(RemoveAction::new(d, scope))
+                    .with_pattern(Some(sq.position()), DigitSet::from_iter(Some(d)));
+                input.event_queue.push_back(event);
+            }
         }
     }
 }
@@ -33,6 +34,11 @@ impl NamedFilter for NakedSingle {
     fn name(&self) -> &'static str {
         "NakedSingle"
     }
+
+    fn difficulty(&self) -> f32 {
+        // 確定した数字を候補から除外するだけなので最も易しい
+        1.0
+    }
 }
 
 impl ScanCandidates for NakedSingle {
@@ -68,6 +74,17 @@ impl ScanCandidates for NakedSingle {
             )
         }
     }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        let positions = Positions::from_iter(input.board.each_columns());
+        for unit in input.board.each_units() {
+            self.remove_digits_in_use(
+                input,
+                ActionScope::Unit(UnitPositions::new(unit, positions)),
+                input.board.unit_items(unit),
+            )
+        }
+    }
 }
 
 pub struct SingleCandidate;
@@ -83,13 +100,16 @@ impl SingleCandidate {
         F: Fn(Positions) -> ActionScope,
     {
         // 各数字について、どの位置に候補が存在するかするかスキャンする
-        context.collect_digit_positions_matches(candidates, |_, ps| ps.num_set() == 1);
+        let candidates = candidates.collect::<Vec<_>>();
+        context
+            .collect_digit_positions_matches(candidates.iter().copied(), |_, ps| ps.num_set() == 1);
 
         // 単一候補以外の数値を除外する。
         for (digit, positions) in context.digit_positions.iter() {
             let scope = scope_fn(*positions);
             let action = RetainAction::with_digit(*digit, scope);
-            let event = Event::from(action);
+            let cells = positions.items_from_iter(candidates.iter().map(|c| c.position()));
+            let event = Event::from(action).with_pattern(cells, DigitSet::from_iter(Some(*digit)));
             event_queue.push_back(event);
         }
     }
@@ -99,6 +119,10 @@ impl NamedFilter for SingleCandidate {
     fn name(&self) -> &'static str {
         "SingleCandidate"
     }
+
+    fn difficulty(&self) -> f32 {
+        1.5
+    }
 }
 
 impl ScanCandidates for SingleCandidate {
@@ -134,4 +158,15 @@ impl ScanCandidates for SingleCandidate {
             );
         }
     }
+
+    fn scan_units(&self, input: &mut FilterInput) {
+        for unit in input.board.each_units() {
+            self.scan_single_candidate(
+                input.context,
+                input.event_queue,
+                input.candidates.unit_items(unit),
+                |positions| ActionScope::Unit(UnitPositions::new(unit, positions)),
+            );
+        }
+    }
 }
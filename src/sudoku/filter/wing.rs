@@ -0,0 +1,301 @@
+use std::iter::FromIterator;
+
+use itertools::Itertools;
+
+use crate::sudoku::action::{ActionScope, RemoveAction};
+use crate::sudoku::board::ItemPosition;
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::{Event, EventQueue};
+use crate::sudoku::filter::{FilterCandidates, FilterInput, NamedFilter};
+use crate::sudoku::positions::CellPositions;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::Board;
+
+fn cells_with_num_candidates(
+    candidates: &Board<Candidate>,
+    num_candidates: usize,
+) -> Vec<(ItemPosition, DigitSet)> {
+    candidates
+        .item_positions()
+        .map(|pos| (pos, candidates.item_at(pos).digits()))
+        .filter(|(_, digits)| digits.len() == num_candidates)
+        .collect()
+}
+
+fn push_remove_events(
+    event_queue: &mut EventQueue,
+    digits: DigitSet,
+    cells: CellPositions,
+    pattern: &[(ItemPosition, DigitSet)],
+) {
+    if cells.is_empty() {
+        return;
+    }
+
+    let pattern_digits = pattern
+        .iter()
+        .fold(DigitSet::default(), |digits, (_, ds)| digits | *ds);
+    for d in digits {
+        let action = RemoveAction::new(d, ActionScope::Cells(cells));
+        let event =
+            Event::from(action).with_pattern(pattern.iter().map(|(pos, _)| *pos), pattern_digits);
+        event_queue.push_back(event);
+    }
+}
+
+pub struct XYWing;
+
+impl NamedFilter for XYWing {
+    fn name(&self) -> &'static str {
+        "XYWing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.2
+    }
+}
+
+impl FilterCandidates for XYWing {
+    fn filter_candidates(&self, input: FilterInput) {
+        let candidates = input.candidates;
+        let bivalues = cells_with_num_candidates(candidates, 2);
+
+        for (pivot, pivot_digits) in bivalues.iter() {
+            let peers = candidates.peer_positions(*pivot);
+            let pincers = bivalues
+                .iter()
+                .filter(|(pos, digits)| {
+                    peers.contains(candidates.item_index(*pos))
+                        && (*digits & *pivot_digits).len() == 1
+                })
+                .collect_vec();
+
+            for ((a, a_digits), (b, b_digits)) in pincers.into_iter().tuple_combinations() {
+                // ピボットが {x, y}、羽が {x, z}, {y, z} の組み合わせになっているか検証する
+                let z = *a_digits & *b_digits;
+                let xy = (*a_digits & *pivot_digits) | (*b_digits & *pivot_digits);
+                if z.len() != 1 || !(z & *pivot_digits).is_empty() || xy != *pivot_digits {
+                    continue;
+                }
+
+                // 両方の羽から見えるセルから z を除外する
+                let cells = candidates
+                    .peer_positions(*a)
+                    .and(&candidates.peer_positions(*b));
+                let pattern = [(*pivot, *pivot_digits), (*a, *a_digits), (*b, *b_digits)];
+                push_remove_events(input.event_queue, z, cells, &pattern);
+            }
+        }
+    }
+}
+
+pub struct XYZWing;
+
+impl NamedFilter for XYZWing {
+    fn name(&self) -> &'static str {
+        "XYZWing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.4
+    }
+}
+
+impl FilterCandidates for XYZWing {
+    fn filter_candidates(&self, input: FilterInput) {
+        let candidates = input.candidates;
+        let bivalues = cells_with_num_candidates(candidates, 2);
+
+        for (pivot, pivot_digits) in cells_with_num_candidates(candidates, 3) {
+            let peers = candidates.peer_positions(pivot);
+            let pincers = bivalues
+                .iter()
+                .filter(|(pos, digits)| {
+                    peers.contains(candidates.item_index(*pos))
+                        && (*digits & pivot_digits) == *digits
+                })
+                .collect_vec();
+
+            for ((a, a_digits), (b, b_digits)) in pincers.into_iter().tuple_combinations() {
+                // ピボットが {x, y, z}、羽が {x, z}, {y, z} の組み合わせになっているか検証する
+                let z = *a_digits & *b_digits;
+                if z.len() != 1 || *a_digits == *b_digits {
+                    continue;
+                }
+
+                // ピボットと両方の羽から見えるセルから z を除外する
+                let cells = peers
+                    .and(&candidates.peer_positions(*a))
+                    .and(&candidates.peer_positions(*b));
+                let pattern = [(pivot, pivot_digits), (*a, *a_digits), (*b, *b_digits)];
+                push_remove_events(input.event_queue, z, cells, &pattern);
+            }
+        }
+    }
+}
+
+pub struct WWing;
+
+impl WWing {
+    fn strong_links(
+        &self,
+        candidates: &Board<Candidate>,
+        digits: DigitSet,
+    ) -> Vec<(Digit, ItemPosition, ItemPosition)> {
+        // 行・列・ブロック内で候補が2箇所にしかない数字の組を列挙する
+        let mut links = Vec::new();
+        for scope in candidates.scopes() {
+            let positions = candidates.scope_positions(scope);
+            for d in digits {
+                let cells = positions
+                    .iter()
+                    .filter(|pos| candidates.item_at(**pos).contains(d))
+                    .collect_vec();
+                if cells.len() == 2 {
+                    links.push((d, *cells[0], *cells[1]));
+                }
+            }
+        }
+        links
+    }
+}
+
+impl NamedFilter for WWing {
+    fn name(&self) -> &'static str {
+        "WWing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.4
+    }
+}
+
+impl FilterCandidates for WWing {
+    fn filter_candidates(&self, input: FilterInput) {
+        let candidates = input.candidates;
+        let bivalues = cells_with_num_candidates(candidates, 2);
+        let digits = bivalues
+            .iter()
+            .fold(DigitSet::default(), |digits, (_, ds)| digits | *ds);
+        let links = self.strong_links(candidates, digits);
+
+        for ((a, a_digits), (b, b_digits)) in bivalues.iter().tuple_combinations() {
+            let a_peers = candidates.peer_positions(*a);
+            let b_peers = candidates.peer_positions(*b);
+            if a_digits != b_digits || a_peers.contains(candidates.item_index(*b)) {
+                continue;
+            }
+
+            for (x, p, q) in links.iter().filter(|(x, _, _)| a_digits.contains(*x)) {
+                if [p, q].iter().any(|pos| **pos == *a || **pos == *b) {
+                    continue;
+                }
+
+                // 強リンクの両端がそれぞれ A, B から見えていれば、A, B のどちらかは y で確定する
+                let (i, j) = (candidates.item_index(*p), candidates.item_index(*q));
+                let linked = (a_peers.contains(i) && b_peers.contains(j))
+                    || (a_peers.contains(j) && b_peers.contains(i));
+                if linked {
+                    let y = a_digits.iter().filter(|d| d != x).collect::<DigitSet>();
+                    let link = DigitSet::from_iter(Some(*x));
+                    let pattern = [(*a, *a_digits), (*b, *b_digits), (*p, link), (*q, link)];
+                    push_remove_events(input.event_queue, y, a_peers.and(&b_peers), &pattern);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::ItemPosition;
+    use crate::sudoku::candidate::Candidate;
+    use crate::sudoku::digit::Digit;
+    use crate::sudoku::filter::tests::{apply_filter, load_candidates};
+    use crate::sudoku::filter::{WWing, XYWing, XYZWing};
+    use crate::sudoku::Board;
+
+    fn digits_at(candidates: &Board<Candidate>, row: usize, col: usize) -> Vec<u8> {
+        candidates
+            .item_at(ItemPosition { row, col })
+            .digits_iter()
+            .map(|d: Digit| d.get())
+            .collect()
+    }
+
+    #[test]
+    fn test_xy_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "045670890",
+            "030020000",
+            "000000000",
+            "000000000",
+            "014560789",
+            "000000000",
+            "000000000",
+            "000000000",
+            "000000000",
+        ]);
+
+        // ピボット r1c1 {1, 2}、羽 r1c6 {1, 3}, r5c1 {2, 3}
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 0));
+        assert_eq!(vec![1, 3], digits_at(&candidates, 0, 5));
+        assert_eq!(vec![2, 3], digits_at(&candidates, 4, 0));
+        assert_eq!(vec![2, 3], digits_at(&candidates, 4, 5));
+        assert!(apply_filter(&XYWing, &board, &mut candidates));
+
+        assert_eq!(vec![2], digits_at(&candidates, 4, 5));
+    }
+
+    #[test]
+    fn test_xyz_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "004560789",
+            "000000000",
+            "000000000",
+            "001000000",
+            "005000000",
+            "006002000",
+            "007000000",
+            "008000000",
+            "009000000",
+        ]);
+
+        // ピボット r1c1 {1, 2, 3}、羽 r1c6 {1, 3}, r3c3 {2, 3}
+        assert_eq!(vec![1, 2, 3], digits_at(&candidates, 0, 0));
+        assert_eq!(vec![1, 3], digits_at(&candidates, 0, 5));
+        assert_eq!(vec![2, 3], digits_at(&candidates, 2, 2));
+        assert_eq!(vec![1, 2, 3], digits_at(&candidates, 0, 1));
+        assert!(apply_filter(&XYZWing, &board, &mut candidates));
+
+        // r1c2 もピボットになるので、r1c1 と r1c2 の両方から 3 が除外される
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 1));
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 0));
+        assert_eq!(vec![1, 3], digits_at(&candidates, 0, 5));
+    }
+
+    #[test]
+    fn test_w_wing() {
+        let (board, mut candidates) = load_candidates(&[
+            "034506780",
+            "090000003",
+            "000000004",
+            "000900006",
+            "067803450",
+            "000000007",
+            "000000010",
+            "000000000",
+            "000000000",
+        ]);
+
+        // r1c1, r5c5 が {1, 2}、c9 の 1 が r1c9, r5c9 の強リンクになっている
+        assert_eq!(vec![1, 2], digits_at(&candidates, 0, 0));
+        assert_eq!(vec![1, 2], digits_at(&candidates, 4, 4));
+        assert_eq!(vec![1, 2, 9], digits_at(&candidates, 0, 4));
+        assert!(apply_filter(&WWing, &board, &mut candidates));
+
+        assert_eq!(vec![1, 9], digits_at(&candidates, 0, 4));
+        assert_eq!(vec![1, 9], digits_at(&candidates, 4, 0));
+    }
+}
@@ -0,0 +1,786 @@
+use itertools::Itertools;
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::sudoku::board::{
+    block_shape, Board, BoardLoader, BoardParseError, ItemPosition, SourceLine, MAX_BOARD_WIDTH,
+};
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::Square;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BoardFormat {
+    // 1行に1問、81 文字
+    Line,
+    // SadMan Software の .sdk
+    Sdk,
+    // Simple Sudoku の .ss、'|' と '-' で区切る
+    SimpleSudoku,
+    // Project Euler の "Grid NN" で区切られた複数問
+    ProjectEuler,
+}
+
+fn is_board_width(n: usize) -> bool {
+    block_shape(n).is_some()
+}
+
+fn is_separator(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c == '-' || c == '+')
+}
+
+// 候補グリッドの罫線に使われる文字
+fn is_pencil_mark_border(c: char) -> bool {
+    matches!(c, '.' | '-' | '+' | ':' | '\'' | '|' | '=')
+}
+
+fn content_lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .map(str::trim_end)
+        .enumerate()
+        .map(|(i, s)| (i + 1, s))
+        .filter(|(_, s)| !s.is_empty() && !s.starts_with('#'))
+}
+
+impl BoardFormat {
+    pub fn detect(s: &str) -> Option<BoardFormat> {
+        let lines = content_lines(s).map(|(_, s)| s).collect_vec();
+        let first = lines.first()?;
+
+        if lines.iter().any(|s| s.starts_with("Grid")) {
+            return Some(BoardFormat::ProjectEuler);
+        }
+        if lines.iter().any(|s| s.contains('|') || is_separator(s)) {
+            return Some(BoardFormat::SimpleSudoku);
+        }
+
+        // 1行の長さが盤面の幅で、行数がその倍数なら .sdk とみなす
+        let width = first.chars().count();
+        // 81 文字の行が並んでいても 81x81 の盤面とはみなさない
+        if is_board_width(width) && lines.len() % width == 0 {
+            Some(BoardFormat::Sdk)
+        } else {
+            Some(BoardFormat::Line)
+        }
+    }
+}
+
+impl BoardLoader {
+    pub fn load(s: &str) -> Result<Vec<Board<Square>>, BoardParseError> {
+        let format = BoardFormat::detect(s).ok_or(BoardParseError::UnknownFormat)?;
+        Self::load_with_format(s, format)
+    }
+
+    pub fn load_with_format(
+        s: &str,
+        format: BoardFormat,
+    ) -> Result<Vec<Board<Square>>, BoardParseError> {
+        match format {
+            BoardFormat::Line => Self::load_one_line(s),
+            BoardFormat::Sdk => {
+                Self::load_grids(content_lines(s).map(|(line, s)| SourceLine::new(line, s)))
+            }
+            BoardFormat::SimpleSudoku => {
+                Self::load_grids(content_lines(s).filter(|(_, s)| !is_separator(s)).map(
+                    |(line, s)| {
+                        let mut source = SourceLine::new(line, s);
+                        source.chars.retain(|(_, c)| *c != '|');
+                        source
+                    },
+                ))
+            }
+            BoardFormat::ProjectEuler => Self::load_project_euler(s),
+        }
+    }
+
+    fn load_one_line(s: &str) -> Result<Vec<Board<Square>>, BoardParseError> {
+        content_lines(s)
+            .map(|(line, s)| {
+                let source = SourceLine::new(line, s);
+                let width = (source.chars.len() as f64).sqrt().round() as usize;
+                if width * width != source.chars.len() {
+                    return Err(BoardParseError::WrongLineLength {
+                        line,
+                        expected: width * width,
+                        found: source.chars.len(),
+                    });
+                }
+
+                let lines = source
+                    .chars
+                    .chunks(width)
+                    .map(|chars| SourceLine {
+                        line,
+                        chars: chars.to_vec(),
+                    })
+                    .collect();
+                Self::from_source_lines(lines)
+            })
+            .collect()
+    }
+
+    fn load_grids(
+        lines: impl Iterator<Item = SourceLine>,
+    ) -> Result<Vec<Board<Square>>, BoardParseError> {
+        // 先頭行の長さを盤面の幅として、幅と同じ行数ずつ区切る
+        let mut lines = lines.peekable();
+        let width = match lines.peek() {
+            Some(source) => source.chars.len(),
+            None => return Ok(Vec::new()),
+        };
+
+        lines
+            .chunks(width)
+            .into_iter()
+            .map(|chunk| Self::from_source_lines(chunk.collect()))
+            .collect()
+    }
+
+    fn load_project_euler(s: &str) -> Result<Vec<Board<Square>>, BoardParseError> {
+        let mut grids: Vec<Vec<SourceLine>> = Vec::new();
+        for (line, s) in content_lines(s) {
+            if s.starts_with("Grid") {
+                grids.push(Vec::new());
+            } else {
+                match grids.last_mut() {
+                    Some(grid) => grid.push(SourceLine::new(line, s)),
+                    None => grids.push(vec![SourceLine::new(line, s)]),
+                }
+            }
+        }
+
+        grids.into_iter().map(Self::from_source_lines).collect()
+    }
+}
+
+impl BoardLoader {
+    pub fn load_pencil_marks(s: &str) -> Result<Board<Candidate>, BoardParseError> {
+        let mut cells = Vec::new();
+        for (line, s) in s.lines().enumerate().map(|(i, s)| (i + 1, s)) {
+            // 罫線だけの行は読み飛ばす
+            if s.chars()
+                .all(|c| c.is_whitespace() || is_pencil_mark_border(c))
+            {
+                continue;
+            }
+
+            let mut digits: Option<DigitSet> = None;
+            for (column, c) in s.chars().enumerate().map(|(i, c)| (i + 1, c)) {
+                if c.is_whitespace() || c == '|' {
+                    cells.extend(digits.take());
+                    continue;
+                }
+
+                let d = Digit::try_from(c).map_err(|_| BoardParseError::InvalidCharacter {
+                    line,
+                    column,
+                    found: c,
+                })?;
+                digits.get_or_insert_with(DigitSet::default).set(d);
+            }
+            cells.extend(digits.take());
+        }
+
+        let width = (cells.len() as f64).sqrt() as usize;
+        let (block_height, block_width) = match block_shape(width) {
+            Some(shape) if width * width == cells.len() => shape,
+            _ => return Err(BoardParseError::WrongCellCount { found: cells.len() }),
+        };
+
+        let items = cells
+            .into_iter()
+            .enumerate()
+            .map(|(i, digits)| Candidate::with_digits(digits, i / width, i % width))
+            .collect();
+        Ok(Board::new(items, block_height, block_width))
+    }
+
+    // 数字の盤面に続けて、ブロックを1文字のラベルで描いた盤面からジグソーサドクを読み込む
+    pub fn load_jigsaw(s: &str) -> Result<Board<Square>, BoardParseError> {
+        let lines = content_lines(s).collect_vec();
+        let width = lines
+            .first()
+            .map(|(_, s)| s.chars().count())
+            .ok_or(BoardParseError::UnknownFormat)?;
+        if lines.len() != 2 * width {
+            return Err(BoardParseError::WrongSize {
+                height: lines.len(),
+            });
+        }
+
+        if width == 0 || width > MAX_BOARD_WIDTH {
+            return Err(BoardParseError::WrongSize { height: width });
+        }
+
+        // ブロックは領域で決まるので、長方形に分けられない 5x5 や 7x7 も読み込める。
+        // 長方形に分けられない大きさでは、盤面の形を 1 行ずつのブロックとして扱う
+        let (block_height, block_width) = block_shape(width).unwrap_or((1, width));
+        let (digits, regions) = lines.split_at(width);
+        let mut board = Self::from_source_lines_with_shape(
+            digits
+                .iter()
+                .map(|(line, s)| SourceLine::new(*line, s))
+                .collect(),
+            block_height,
+            block_width,
+        )?;
+
+        let mut labels: BTreeMap<char, Vec<usize>> = BTreeMap::new();
+        for (row, (line, s)) in regions.iter().enumerate() {
+            let found = s.chars().count();
+            if found != width {
+                return Err(BoardParseError::WrongLineLength {
+                    line: *line,
+                    expected: width,
+                    found,
+                });
+            }
+
+            for (col, c) in s.chars().enumerate() {
+                labels.entry(c).or_default().push(row * width + col);
+            }
+        }
+
+        if labels.len() != width {
+            return Err(BoardParseError::WrongRegionCount {
+                expected: width,
+                found: labels.len(),
+            });
+        }
+
+        // ラベルの順にブロック番号を振る
+        let mut cells = vec![0; width * width];
+        for (block, (label, indexes)) in labels.into_iter().enumerate() {
+            if indexes.len() != width {
+                return Err(BoardParseError::WrongRegionSize {
+                    label,
+                    expected: width,
+                    found: indexes.len(),
+                });
+            }
+            for i in indexes {
+                cells[i] = block;
+            }
+        }
+        board.set_regions(cells);
+        Ok(board)
+    }
+
+    // ケージを1文字のラベルで描いた盤面と、"ラベル=合計" の行からキラーサドクを読み込む
+    pub fn load_killer(s: &str) -> Result<Board<Square>, BoardParseError> {
+        let lines = content_lines(s).collect_vec();
+        let width = lines
+            .first()
+            .map(|(_, s)| s.chars().count())
+            .ok_or(BoardParseError::UnknownFormat)?;
+        let (block_height, block_width) =
+            block_shape(width).ok_or(BoardParseError::WrongSize { height: width })?;
+        if lines.len() < width {
+            return Err(BoardParseError::WrongSize {
+                height: lines.len(),
+            });
+        }
+
+        let (grid, sums) = lines.split_at(width);
+        let mut cages: BTreeMap<char, Vec<ItemPosition>> = BTreeMap::new();
+        for (row, (line, s)) in grid.iter().enumerate() {
+            let found = s.chars().count();
+            if found != width {
+                return Err(BoardParseError::WrongLineLength {
+                    line: *line,
+                    expected: width,
+                    found,
+                });
+            }
+
+            // '.' はどのケージにも属さない
+            for (col, c) in s.chars().enumerate().filter(|(_, c)| *c != '.') {
+                cages.entry(c).or_default().push(ItemPosition { row, col });
+            }
+        }
+
+        let mut cage_sums = BTreeMap::new();
+        for (line, token) in sums
+            .iter()
+            .flat_map(|(line, s)| s.split_whitespace().map(move |t| (*line, t)))
+        {
+            // 1行に "a=12 b=7" のように複数並べてもよい
+            let parsed = token.split_once('=').and_then(|(label, sum)| {
+                let label = label.chars().exactly_one().ok()?;
+                let sum = sum.parse::<u32>().ok()?;
+                Some((label, sum))
+            });
+            match parsed {
+                Some((label, sum)) if cages.contains_key(&label) => {
+                    cage_sums.insert(label, sum);
+                }
+                _ => return Err(BoardParseError::InvalidCageSum { line }),
+            }
+        }
+
+        let items = (0..width)
+            .cartesian_product(0..width)
+            .map(|(row, col)| Square::new(None, row, col))
+            .collect();
+        let mut board = Board::new(items, block_height, block_width);
+        for (label, cells) in cages {
+            let sum = cage_sums
+                .get(&label)
+                .ok_or(BoardParseError::MissingCageSum { label })?;
+            if cells.len() > width {
+                return Err(BoardParseError::WrongCageSize {
+                    label,
+                    found: cells.len(),
+                });
+            }
+            board.add_cage(*sum, cells);
+        }
+        Ok(board)
+    }
+}
+
+pub struct BoardWriter {}
+
+impl BoardWriter {
+    pub fn write(boards: &[Board<Square>], format: BoardFormat) -> String {
+        let separator = match format {
+            BoardFormat::Line | BoardFormat::ProjectEuler => "",
+            BoardFormat::Sdk | BoardFormat::SimpleSudoku => "\n",
+        };
+
+        boards
+            .iter()
+            .enumerate()
+            .map(|(i, board)| Self::write_board(board, format, i + 1))
+            .join(separator)
+    }
+
+    pub fn write_board(board: &Board<Square>, format: BoardFormat, no: usize) -> String {
+        // 幅が 10 以上の盤面では '0' を空白として読み込めない
+        let blank = match format {
+            BoardFormat::ProjectEuler if board.width() <= 9 => '0',
+            _ => '.',
+        };
+        let row_chars = |row: usize| {
+            board
+                .row_items(row)
+                .map(|sq| sq.digit().map(char::from).unwrap_or(blank))
+                .collect_vec()
+        };
+
+        let mut lines = Vec::new();
+        match format {
+            BoardFormat::Line => {
+                lines.push(board.each_rows().flat_map(row_chars).collect::<String>());
+            }
+            BoardFormat::Sdk => {
+                lines.extend(board.each_rows().map(|row| row_chars(row).iter().collect()));
+            }
+            BoardFormat::SimpleSudoku => {
+                let width = board.width() + board.num_block_columns() - 1;
+                for row in board.each_rows() {
+                    if row > 0 && row % board.block_height() == 0 {
+                        lines.push("-".repeat(width));
+                    }
+                    let chars = row_chars(row);
+                    lines.push(
+                        chars
+                            .chunks(board.block_width())
+                            .map(|chunk| chunk.iter().collect::<String>())
+                            .join("|"),
+                    );
+                }
+            }
+            BoardFormat::ProjectEuler => {
+                lines.push(format!("Grid {:02}", no));
+                lines.extend(board.each_rows().map(|row| row_chars(row).iter().collect()));
+            }
+        }
+
+        lines.into_iter().map(|s| s + "\n").collect()
+    }
+
+    pub fn write_pencil_marks(board: &Board<Square>, candidates: &Board<Candidate>) -> String {
+        let cell = |pos| match board.item_at(pos).digit() {
+            Some(d) => char::from(d).to_string(),
+            None => candidates
+                .item_at(pos)
+                .digits_iter()
+                .map(char::from)
+                .collect(),
+        };
+        let rows = board
+            .each_rows()
+            .map(|row| {
+                board
+                    .each_columns()
+                    .map(|col| cell(ItemPosition { row, col }))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // 列ごとに最大の幅に揃える
+        let widths = board
+            .each_columns()
+            .map(|col| rows.iter().map(|r| r[col].len()).max().unwrap_or(1))
+            .collect_vec();
+
+        let block_width = board.block_width();
+        let border = |left: char, middle: char, right: char| {
+            let stacks = widths
+                .chunks(block_width)
+                .map(|ws| "-".repeat(ws.iter().map(|w| w + 1).sum::<usize>() + 1))
+                .join(&middle.to_string());
+            format!("{}{}{}\n", left, stacks, right)
+        };
+
+        let mut s = border('.', '.', '.');
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 && i % board.block_height() == 0 {
+                s += &border(':', '+', ':');
+            }
+            let stacks = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .chunks(block_width)
+                .into_iter()
+                .map(|mut cells| format!(" {} ", cells.join(" ")))
+                .join("|");
+            s += &format!("|{}|\n", stacks);
+        }
+        s += &border('\'', '\'', '\'');
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::sudoku::board::{BoardParseError, ItemPosition};
+    use crate::sudoku::format::{BoardFormat, BoardWriter};
+    use crate::sudoku::BoardLoader;
+
+    const LINE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..\n";
+
+    const SDK: &str = "#A project euler
+..3.2.6..
+9..3.5..1
+..18.64..
+..81.29..
+7.......8
+..67.82..
+..26.95..
+8..2.3..9
+..5.1.3..
+";
+
+    const SS: &str = "..3|.2.|6..
+9..|3.5|..1
+..1|8.6|4..
+-----------
+..8|1.2|9..
+7..|...|..8
+..6|7.8|2..
+-----------
+..2|6.9|5..
+8..|2.3|..9
+..5|.1.|3..
+";
+
+    #[test]
+    fn test_detect() {
+        let euler = include_str!("../../assets/p096_sudoku.txt");
+        assert_eq!(Some(BoardFormat::Line), BoardFormat::detect(LINE));
+        assert_eq!(Some(BoardFormat::Sdk), BoardFormat::detect(SDK));
+        assert_eq!(Some(BoardFormat::SimpleSudoku), BoardFormat::detect(SS));
+        assert_eq!(Some(BoardFormat::ProjectEuler), BoardFormat::detect(euler));
+        assert_eq!(None, BoardFormat::detect("\n\n"));
+
+        let lines = LINE.repeat(81);
+        assert_eq!(Some(BoardFormat::Line), BoardFormat::detect(&lines));
+    }
+
+    #[test]
+    fn test_same_board() {
+        let boards = [LINE, SDK, SS]
+            .iter()
+            .map(|s| BoardLoader::load(s).unwrap())
+            .collect_vec();
+
+        let euler = include_str!("../../assets/p096_sudoku.txt");
+        let first = BoardLoader::load(euler).unwrap().remove(0);
+        for board in boards.iter().flatten() {
+            assert!(board.items().eq(first.items()));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (s, format) in [(LINE, BoardFormat::Line), (SS, BoardFormat::SimpleSudoku)].iter() {
+            let boards = BoardLoader::load(s).unwrap();
+            assert_eq!(*s, BoardWriter::write(&boards, *format));
+        }
+
+        // .sdk のコメントは保存されない
+        let boards = BoardLoader::load(SDK).unwrap();
+        let sdk = BoardWriter::write(&boards, BoardFormat::Sdk);
+        assert_eq!(SDK.lines().skip(1).join("\n") + "\n", sdk);
+
+        let euler = include_str!("../../assets/p096_sudoku.txt");
+        let boards = BoardLoader::load(euler).unwrap();
+        assert_eq!(50, boards.len());
+        let written = BoardWriter::write(&boards, BoardFormat::ProjectEuler);
+        assert_eq!(euler.trim_end(), written.trim_end());
+
+        // 書き出した内容を別の形式で読み直しても同じ盤面になる
+        for format in [
+            BoardFormat::Line,
+            BoardFormat::Sdk,
+            BoardFormat::SimpleSudoku,
+        ]
+        .iter()
+        {
+            let loaded = BoardLoader::load(&BoardWriter::write(&boards, *format)).unwrap();
+            assert_eq!(boards.len(), loaded.len());
+            for (a, b) in boards.iter().zip(loaded.iter()) {
+                assert!(a.items().eq(b.items()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let line = &LINE[..80];
+        assert_eq!(
+            Err(BoardParseError::WrongLineLength {
+                line: 1,
+                expected: 81,
+                found: 80
+            }),
+            BoardLoader::load_with_format(line, BoardFormat::Line).map(|_| ())
+        );
+
+        let ss = SS.replacen("9..|3.5|..1", "9..|3x5|..1", 1);
+        assert_eq!(
+            Err(BoardParseError::InvalidCharacter {
+                line: 2,
+                column: 6,
+                found: 'x'
+            }),
+            BoardLoader::load(&ss).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::UnknownFormat),
+            BoardLoader::load("").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_killer() {
+        let s = "# killer
+aab.
+cdb.
+cde.
+ffee
+a=3 b=7
+c=5
+d=5 e=8 f=7
+";
+        let board = BoardLoader::load_killer(s).unwrap();
+        assert_eq!(4, board.width());
+        assert!(board.items().all(|sq| sq.digit().is_none()));
+        assert_eq!(6, board.cages().len());
+        assert_eq!(3, board.cages()[0].sum());
+        assert_eq!(&[0, 1], board.cages()[0].cells());
+        assert_eq!(Some(4), board.cage_of(ItemPosition { row: 3, col: 3 }));
+        assert_eq!(None, board.cage_of(ItemPosition { row: 0, col: 3 }));
+
+        assert_eq!(
+            Err(BoardParseError::InvalidCageSum { line: 7 }),
+            BoardLoader::load_killer(&s.replace("c=5", "c:5")).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::InvalidCageSum { line: 7 }),
+            BoardLoader::load_killer(&s.replace("c=5", "x=5")).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::MissingCageSum { label: 'c' }),
+            BoardLoader::load_killer(&s.replace("c=5", "")).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::WrongCageSize {
+                label: 'f',
+                found: 5
+            }),
+            BoardLoader::load_killer(
+                &s.replace("ffee", "ffff")
+                    .replace("cde.", "cdf.")
+                    .replace(" e=8", "")
+            )
+            .map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::WrongLineLength {
+                line: 3,
+                expected: 4,
+                found: 3
+            }),
+            BoardLoader::load_killer(&s.replace("cdb.", "cdb")).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_jigsaw() {
+        let s = "1...
+..3.
+.2..
+...4
+# ブロック
+aabb
+acbb
+acdd
+ccdd
+";
+        let board = BoardLoader::load_jigsaw(s).unwrap();
+        assert!(board.is_jigsaw());
+        assert_eq!(
+            Some(&[0, 0, 1, 1, 0, 2, 1, 1, 0, 2, 3, 3, 2, 2, 3, 3][..]),
+            board.regions()
+        );
+
+        assert_eq!(
+            Err(BoardParseError::WrongSize { height: 7 }),
+            BoardLoader::load_jigsaw(&s.replace("ccdd\n", "")).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::WrongRegionCount {
+                expected: 4,
+                found: 5
+            }),
+            BoardLoader::load_jigsaw(&s.replace("ccdd", "ccde")).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::WrongRegionSize {
+                label: 'c',
+                expected: 4,
+                found: 5
+            }),
+            BoardLoader::load_jigsaw(&s.replace("ccdd", "cccd")).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_large_symbols() {
+        // 10 以上の数字は英字で表し、小文字でも読み込める
+        let sdk = ".8.7.G.C.1.....D\n\
+.....F9.3.GC...1\n\
+56...B..7.....3.\n\
+G.2.A..6...E....\n\
+39F.....6......B\n\
+415....D8.3.A.C.\n\
+A..C6..1.B..3..F\n\
+.......9.G.2...5\n\
+E.4...7B93.F..2.\n\
+.B...C..2.6.E514\n\
+.F39........8..7\n\
+..A2..45.7..C..3\n\
+...B...3.6...4..\n\
+2.C.G.........B8\n\
+D..5.98...231AG6\n\
+.A.G5.E...9.2.F.\n";
+        let boards = BoardLoader::load(&sdk.to_lowercase()).unwrap();
+        assert_eq!(16, boards[0].width());
+        assert_eq!(
+            Some(16),
+            boards[0]
+                .item_at(ItemPosition { row: 0, col: 5 })
+                .digit()
+                .map(|d| d.get())
+        );
+        assert_eq!(sdk, BoardWriter::write(&boards, BoardFormat::Sdk));
+
+        let ss = BoardWriter::write(&boards, BoardFormat::SimpleSudoku);
+        let loaded = BoardLoader::load(&ss).unwrap();
+        assert!(boards[0].items().eq(loaded[0].items()));
+
+        // 盤面の幅を超える数字は読み込めない
+        let line = LINE.replacen("3", "A", 1);
+        assert_eq!(
+            Err(BoardParseError::InvalidCharacter {
+                line: 1,
+                column: 3,
+                found: 'A'
+            }),
+            BoardLoader::load(&line).map(|_| ())
+        );
+
+        // 0-F の16進表記は受け付けない
+        let hex = sdk.replacen('.', "0", 1);
+        assert_eq!(
+            Err(BoardParseError::HexDigit { line: 1, column: 1 }),
+            BoardLoader::load(&hex).map(|_| ())
+        );
+        let euler = BoardWriter::write(&boards, BoardFormat::ProjectEuler);
+        let loaded = BoardLoader::load(&euler).unwrap();
+        assert!(boards[0].items().eq(loaded[0].items()));
+    }
+
+    #[test]
+    fn test_pencil_marks() {
+        let plain = "17 4 3 9 8 67 2 5 167
+6 789 1789 4 2 5 138 38 178
+2 578 578 37 367 1 368 9 4
+9 568 2568 13 135 4 1568 7 1268
+3 57 257 6 157 8 1459 24 129
+4 1 5678 2 57 9 568 68 3
+8 2 1679 5 13467 367 3469 346 69
+17 679 1679 137 13467 2367 34689 23468 5
+5 3 4 8 9 26 7 1 26
+";
+        let candidates = BoardLoader::load_pencil_marks(plain).unwrap();
+        let board = candidates.squares();
+        assert_eq!(9, candidates.width());
+        assert_eq!(
+            vec![1, 3, 4, 6, 7],
+            candidates
+                .item_at(ItemPosition { row: 6, col: 4 })
+                .digits_iter()
+                .map(|d| d.get())
+                .collect_vec()
+        );
+
+        // 罫線付きで書き出して読み直すと、同じ候補になる
+        let written = BoardWriter::write_pencil_marks(&board, &candidates);
+        assert!(written.starts_with(".-------------.-"));
+        assert!(written.contains("| 17 4   3    | 9   8     67   |"));
+
+        let loaded = BoardLoader::load_pencil_marks(&written).unwrap();
+        assert!(candidates
+            .items()
+            .zip(loaded.items())
+            .all(|(a, b)| a.digits() == b.digits()));
+        assert_eq!(
+            written,
+            BoardWriter::write_pencil_marks(&loaded.squares(), &loaded)
+        );
+
+        let wrong = plain.replacen("1679", "1x79", 1);
+        assert_eq!(
+            Err(BoardParseError::InvalidCharacter {
+                line: 7,
+                column: 6,
+                found: 'x'
+            }),
+            BoardLoader::load_pencil_marks(&wrong).map(|_| ())
+        );
+        assert_eq!(
+            Err(BoardParseError::WrongCellCount { found: 72 }),
+            BoardLoader::load_pencil_marks(&plain.lines().take(8).join("\n")).map(|_| ())
+        );
+    }
+}
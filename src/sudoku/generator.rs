@@ -0,0 +1,274 @@
+use std::ops::RangeInclusive;
+
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use thiserror::Error;
+
+use crate::sudoku::board::{Board, ItemPosition};
+use crate::sudoku::digit::Digit;
+use crate::sudoku::dlx::ExactCover;
+use crate::sudoku::solver::Solver;
+use crate::sudoku::Square;
+
+const DEFAULT_MAX_ATTEMPTS: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("no puzzle in the difficulty band after {attempts} attempts")]
+    Exhausted { attempts: usize },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Symmetry {
+    None,
+    Rotational,
+    Mirror,
+}
+
+impl Symmetry {
+    fn positions(self, pos: ItemPosition, size: usize) -> Vec<ItemPosition> {
+        let mirrored = match self {
+            Symmetry::None => pos,
+            Symmetry::Rotational => ItemPosition {
+                row: size - 1 - pos.row,
+                col: size - 1 - pos.col,
+            },
+            Symmetry::Mirror => ItemPosition {
+                row: pos.row,
+                col: size - 1 - pos.col,
+            },
+        };
+
+        if mirrored == pos {
+            vec![pos]
+        } else {
+            vec![pos, mirrored]
+        }
+    }
+}
+
+pub struct Generator<R: Rng> {
+    rng: R,
+    block_height: usize,
+    block_width: usize,
+    symmetry: Symmetry,
+    difficulty: RangeInclusive<f32>,
+    max_attempts: usize,
+}
+
+impl Generator<StdRng> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> Generator<R> {
+    pub fn new(rng: R) -> Self {
+        Generator {
+            rng,
+            block_height: 3,
+            block_width: 3,
+            symmetry: Symmetry::None,
+            difficulty: 0.0..=f32::MAX,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    // ブロックの縦横のセル数、盤面の一辺は height * width になる
+    pub fn block_size(mut self, height: usize, width: usize) -> Self {
+        self.block_height = height;
+        self.block_width = width;
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    pub fn difficulty(mut self, difficulty: RangeInclusive<f32>) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn generate(&mut self) -> Result<Board<Square>, GeneratorError> {
+        for _ in 0..self.max_attempts {
+            let solution = self.complete_board();
+            let puzzle = self.remove_clues(solution);
+            if self.difficulty.contains(&rate(&puzzle)) {
+                return Ok(puzzle);
+            }
+        }
+
+        Err(GeneratorError::Exhausted {
+            attempts: self.max_attempts,
+        })
+    }
+
+    fn empty_board(&self) -> Board<Square> {
+        let size = self.block_height * self.block_width;
+        let items = (0..size)
+            .cartesian_product(0..size)
+            .map(|(row, col)| Square::new(None, row, col))
+            .collect();
+        Board::new(items, self.block_height, self.block_width)
+    }
+
+    fn complete_board(&mut self) -> Board<Square> {
+        // 対角線上のブロックは互いに独立なので、ランダムに埋めてから残りを解く
+        // ブロックが小さいと解のない埋め方もあるので、そのときは埋め直す
+        loop {
+            let mut board = self.empty_board();
+            let size = board.width();
+            let num_diagonals = board.num_block_rows().min(board.num_block_columns());
+            for block in 0..num_diagonals {
+                let mut digits = (1..=size as u8).map(Digit::from).collect_vec();
+                digits.shuffle(&mut self.rng);
+
+                let positions = (0..self.block_height).cartesian_product(0..self.block_width);
+                for ((row, col), digit) in positions.zip(digits) {
+                    let pos = ItemPosition {
+                        row: block * self.block_height + row,
+                        col: block * self.block_width + col,
+                    };
+                    board.item_at_mut(pos).fix_digit(digit);
+                }
+            }
+
+            let mut solution = None;
+            ExactCover::new(&board).search(|digits| {
+                solution = Some(digits.to_vec());
+                false
+            });
+
+            if let Some(solution) = solution {
+                for (cell, digit) in solution {
+                    let pos = board.item_position(cell);
+                    if !board.item_at(pos).is_fixed() {
+                        board.item_at_mut(pos).fix_digit(digit);
+                    }
+                }
+                return board;
+            }
+        }
+    }
+
+    fn remove_clues(&mut self, mut board: Board<Square>) -> Board<Square> {
+        let size = board.width();
+        let mut positions = board.item_positions().collect_vec();
+        positions.shuffle(&mut self.rng);
+
+        for pos in positions {
+            if !board.item_at(pos).is_fixed() {
+                continue;
+            }
+
+            // 対称な位置のヒントもまとめて取り除き、唯一解かつ難易度の上限以内なら採用する
+            let mut next_board = board.clone();
+            for pos in self.symmetry.positions(pos, size) {
+                next_board.item_at_mut(pos).clear();
+            }
+            if next_board.has_unique_solution() && rate(&next_board) <= *self.difficulty.end() {
+                board = next_board;
+            }
+        }
+        board
+    }
+}
+
+pub fn rate(board: &Board<Square>) -> f32 {
+    let report = Solver::new(board.clone()).solve();
+    if report.is_solved() {
+        report.statistics.difficulty()
+    } else {
+        f32::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::{Board, ItemPosition};
+    use crate::sudoku::generator::{rate, Generator, Symmetry};
+    use crate::sudoku::Square;
+
+    #[test]
+    fn test_generate() {
+        let puzzle = Generator::with_seed(96).generate().unwrap();
+        assert!(puzzle.has_unique_solution());
+        assert!(!puzzle.is_complete());
+
+        // 同じシードからは同じ問題が生成される
+        let other = Generator::with_seed(96).generate().unwrap();
+        assert!(puzzle.items().eq(other.items()));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        let puzzle = Generator::with_seed(7)
+            .symmetry(Symmetry::Rotational)
+            .generate()
+            .unwrap();
+        assert!(puzzle.has_unique_solution());
+        assert_rotational(&puzzle);
+    }
+
+    fn assert_rotational(puzzle: &Board<Square>) {
+        let last = puzzle.width() - 1;
+        for pos in puzzle.item_positions() {
+            let rotated = ItemPosition {
+                row: last - pos.row,
+                col: last - pos.col,
+            };
+            assert_eq!(
+                puzzle.item_at(pos).is_fixed(),
+                puzzle.item_at(rotated).is_fixed()
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_size() {
+        // 2x3 ブロックの 6x6 盤面
+        let puzzle = Generator::with_seed(6)
+            .block_size(2, 3)
+            .symmetry(Symmetry::Rotational)
+            .generate()
+            .unwrap();
+        assert_eq!(6, puzzle.width());
+        assert_eq!(6, puzzle.height());
+        assert_eq!(2, puzzle.block_height());
+        assert_eq!(3, puzzle.block_width());
+        assert!(puzzle.has_unique_solution());
+        assert!(!puzzle.is_complete());
+        assert_rotational(&puzzle);
+
+        // 2x2 ブロックでは対角線の埋め方によって解がないので、埋め直して作る
+        for seed in 0..10 {
+            let puzzle = Generator::with_seed(seed)
+                .block_size(2, 2)
+                .generate()
+                .unwrap();
+            assert_eq!(4, puzzle.width());
+            assert!(puzzle.has_unique_solution());
+        }
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let puzzle = Generator::with_seed(1)
+            .difficulty(2.5..=4.0)
+            .generate()
+            .unwrap();
+        assert!(puzzle.has_unique_solution());
+
+        let difficulty = rate(&puzzle);
+        assert!((2.5..=4.0).contains(&difficulty));
+    }
+}
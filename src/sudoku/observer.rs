@@ -0,0 +1,22 @@
+use crate::sudoku::board::ItemPosition;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::Event;
+use crate::sudoku::set::DigitSet;
+
+// Solver の進み具合を外から観察する、必要なものだけ実装すればよい
+pub trait SolverObserver: Send {
+    fn on_filter_start(&mut self, _filter: &'static str) {}
+
+    // changed はそのフィルタで候補が減ったかどうか
+    fn on_filter_end(&mut self, _filter: &'static str, _changed: bool) {}
+
+    fn on_event(&mut self, _event: &Event, _changed: bool) {}
+
+    fn on_fix(&mut self, _pos: ItemPosition, _digit: Digit) {}
+
+    // 行き詰まって pos に digits のいずれかを仮置きする
+    fn on_branch(&mut self, _pos: ItemPosition, _digits: DigitSet) {}
+
+    // 仮置きが矛盾したので、残りの候補に戻る
+    fn on_backtrack(&mut self) {}
+}
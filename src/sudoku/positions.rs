@@ -1,10 +1,15 @@
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::FromIterator;
 
+use anyhow::{anyhow, Error as AnyhowError};
+
 use crate::flags::Flags32;
 use crate::sudoku::board::BlockPosition;
 
 #[derive(Copy, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Vec<usize>", into = "Vec<usize>"))]
 pub struct Positions(Flags32);
 
 impl fmt::Debug for Positions {
@@ -58,6 +63,10 @@ impl Positions {
         Positions(self.0 & other.0)
     }
 
+    pub fn or(&self, other: &Positions) -> Positions {
+        Positions(self.0 | other.0)
+    }
+
     pub fn invert(&self, max_length: usize) -> Positions {
         let mask = (0x01 << max_length) - 1;
         Positions(!self.0 & Flags32::from(mask))
@@ -70,6 +79,116 @@ impl FromIterator<usize> for Positions {
     }
 }
 
+// Flags32 に収まる位置の数
+const MAX_POSITIONS: usize = 32;
+
+impl TryFrom<Vec<usize>> for Positions {
+    type Error = AnyhowError;
+
+    fn try_from(positions: Vec<usize>) -> Result<Self, Self::Error> {
+        match positions.iter().find(|i| **i >= MAX_POSITIONS) {
+            Some(i) => Err(anyhow!(
+                "out of range. must be less than {}, given:{}",
+                MAX_POSITIONS,
+                i
+            )),
+            None => Ok(positions.into_iter().collect()),
+        }
+    }
+}
+
+impl From<Positions> for Vec<usize> {
+    fn from(positions: Positions) -> Self {
+        positions.iter().collect()
+    }
+}
+
+// 25x25 の盤面(625 セル)まで扱えるように 64bit x 10
+const CELL_WORDS: usize = 10;
+const MAX_CELLS: usize = 64 * CELL_WORDS;
+
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+pub struct CellPositions([u64; CELL_WORDS]);
+
+impl fmt::Debug for CellPositions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indexes = self.iter().map(|i| format!("{}", i)).collect::<Vec<_>>();
+        write!(f, "CellPositions({})", indexes.join(", "))
+    }
+}
+
+impl CellPositions {
+    pub fn set(&mut self, index: usize) {
+        assert!(index < MAX_CELLS);
+        self.0[index / 64] |= 0x01 << (index % 64);
+    }
+
+    pub fn unset(&mut self, index: usize) {
+        assert!(index < MAX_CELLS);
+        self.0[index / 64] &= !(0x01 << (index % 64));
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < MAX_CELLS && self.0[index / 64] & (0x01 << (index % 64)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    pub fn num_set(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let cells = *self;
+        (0..CELL_WORDS).flat_map(move |i| {
+            let mut word = cells.0[i];
+            std::iter::from_fn(move || {
+                // 最下位のビットから順に取り出す
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    pub fn and(&self, other: &CellPositions) -> CellPositions {
+        self.zip_with(other, |a, b| a & b)
+    }
+
+    pub fn or(&self, other: &CellPositions) -> CellPositions {
+        self.zip_with(other, |a, b| a | b)
+    }
+
+    fn zip_with(&self, other: &CellPositions, f: impl Fn(u64, u64) -> u64) -> CellPositions {
+        let mut words = [0; CELL_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = f(self.0[i], other.0[i]);
+        }
+        CellPositions(words)
+    }
+
+    pub fn items_from_iter<T>(self, iter: impl Iterator<Item = T>) -> impl Iterator<Item = T> {
+        iter.enumerate()
+            .filter(move |(i, _)| self.contains(*i))
+            .map(|(_, x)| x)
+    }
+}
+
+impl FromIterator<usize> for CellPositions {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut cells = CellPositions::default();
+        for i in iter {
+            cells.set(i);
+        }
+        cells
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct RowPositions {
     pub row: usize,
@@ -114,3 +233,35 @@ impl BlockPositions {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone)]
+pub struct UnitPositions {
+    pub unit: usize,
+    pub indexes: Positions,
+}
+
+impl UnitPositions {
+    pub fn new(unit: usize, positions: Positions) -> Self {
+        UnitPositions {
+            unit,
+            indexes: positions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        use crate::sudoku::positions::Positions;
+
+        let positions = Positions::with_positions(vec![0, 3, 31].into_iter());
+        let json = serde_json::to_string(&positions).unwrap();
+        assert_eq!("[0,3,31]", json);
+        assert!(positions == serde_json::from_str::<Positions>(&json).unwrap());
+
+        // Flags32 に収まらない位置はパニックせずにエラーにする
+        assert!(serde_json::from_str::<Positions>("[32]").is_err());
+    }
+}
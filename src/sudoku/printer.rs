@@ -17,11 +17,93 @@ impl<'a, T: Debug + Clone> BoardPrinter<'a, T> {
     }
 }
 
+impl<'a> BoardPrinter<'a, Square> {
+    // 隣り合うセルが別のグループなら線を引き、label があればセルの上段に表示する
+    fn outlines<G, L>(&self, group_of: G, label: Option<L>) -> String
+    where
+        G: Fn(ItemPosition) -> Option<usize>,
+        L: Fn(ItemPosition) -> String,
+    {
+        let board = self.board;
+        let same_group = |a, b| match (group_of(a), group_of(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        };
+
+        let mut s = String::new();
+        for row in 0..=board.width() {
+            for col in board.each_columns() {
+                let pos = ItemPosition { row, col };
+                let open = row > 0
+                    && row < board.width()
+                    && same_group(ItemPosition { row: row - 1, col }, pos);
+                s.push('+');
+                s.push_str(if open { "   " } else { "---" });
+            }
+            s.push_str("+\n");
+            if row == board.width() {
+                break;
+            }
+
+            let num_lines = if label.is_some() { 2 } else { 1 };
+            for line in (2 - num_lines)..2 {
+                for col in board.each_columns() {
+                    let pos = ItemPosition { row, col };
+                    let open = col > 0 && same_group(ItemPosition { row, col: col - 1 }, pos);
+                    s.push(if open { ' ' } else { '|' });
+
+                    let text = match &label {
+                        Some(label) if line == 0 => label(pos),
+                        _ => format!(" {} ", board.item_at(pos)),
+                    };
+                    s.push_str(&text);
+                }
+                s.push_str("|\n");
+            }
+        }
+        s
+    }
+
+    // ケージの境界を線で描き、各ケージの先頭のセルに合計を表示する
+    pub fn cage_outlines(&self) -> String {
+        let board = self.board;
+        self.outlines(
+            |pos| board.cage_of(pos),
+            Some(|pos| {
+                board
+                    .cage_of(pos)
+                    .map(|cage| &board.cages()[cage])
+                    .filter(|cage| cage.cells()[0] == board.item_index(pos))
+                    .map(|cage| format!("{:<3}", cage.sum()))
+                    .unwrap_or_else(|| "   ".to_string())
+            }),
+        )
+    }
+
+    // ジグソーのブロックの境界を線で描く
+    pub fn region_outlines(&self) -> String {
+        let board = self.board;
+        self.outlines(
+            |pos| Some(board.block_index(board.block_position_of(pos))),
+            None::<fn(ItemPosition) -> String>,
+        )
+    }
+}
+
 impl<'a> Printer for BoardPrinter<'a, Square> {
     fn show(&self) {
+        if !self.board.cages().is_empty() {
+            print!("{}", self.cage_outlines());
+            return;
+        }
+        if self.board.is_jigsaw() {
+            print!("{}", self.region_outlines());
+            return;
+        }
+
         let show_separator = || {
             for col in self.board.each_columns() {
-                if col % self.board.block_size() == 0 {
+                if col % self.board.block_width() == 0 {
                     print!("+-");
                 }
                 print!("--");
@@ -30,11 +112,11 @@ impl<'a> Printer for BoardPrinter<'a, Square> {
         };
 
         for pos in self.board.item_positions() {
-            if pos.row % self.board.block_size() == 0 && pos.col == 0 {
+            if pos.row % self.board.block_height() == 0 && pos.col == 0 {
                 show_separator();
             }
 
-            if pos.col % self.board.block_size() == 0 {
+            if pos.col % self.board.block_width() == 0 {
                 print!("| ");
             }
 
@@ -67,10 +149,10 @@ impl<'a> Printer for BoardPrinter<'a, Candidate> {
                     print!("|");
                     let iter = candidates
                         .possible_digits()
-                        .skip(block_row * self.board.block_size())
-                        .map(|d| Some(d))
+                        .skip(block_row * self.board.block_width())
+                        .map(Some)
                         .chain((0..).map(|_| None))
-                        .take(self.board.block_size());
+                        .take(self.board.block_width());
                     for d in iter {
                         if let Some(d) = d {
                             print!("{:2}", d);
@@ -87,3 +169,66 @@ impl<'a> Printer for BoardPrinter<'a, Candidate> {
         show_separator();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::sudoku::board::ItemPosition;
+    use crate::sudoku::printer::BoardPrinter;
+    use crate::sudoku::BoardLoader;
+
+    #[test]
+    fn test_cage_outlines() {
+        let mut board =
+            BoardLoader::from_lines(["1000", "0000", "0000", "0000"].iter().copied()).unwrap();
+        board.add_cage(
+            3,
+            vec![
+                ItemPosition { row: 0, col: 0 },
+                ItemPosition { row: 0, col: 1 },
+            ],
+        );
+        board.add_cage(
+            7,
+            vec![
+                ItemPosition { row: 1, col: 0 },
+                ItemPosition { row: 2, col: 0 },
+            ],
+        );
+
+        let expected = "\
++---+---+---+---+
+|3      |   |   |
+| 1   - | - | - |
++---+---+---+---+
+|7  |   |   |   |
+| - | - | - | - |
++   +---+---+---+
+|   |   |   |   |
+| - | - | - | - |
++---+---+---+---+
+|   |   |   |   |
+| - | - | - | - |
++---+---+---+---+
+";
+        assert_eq!(expected, BoardPrinter::new(&board).cage_outlines());
+    }
+
+    #[test]
+    fn test_region_outlines() {
+        let s = "1...\n..3.\n.2..\n...4\naabb\nacbb\nacdd\nccdd\n";
+        let board = BoardLoader::load_jigsaw(s).unwrap();
+
+        let expected = "\
++---+---+---+---+
+| 1   - | -   - |
++   +---+   +   +
+| - | - | 3   - |
++   +   +---+---+
+| - | 2 | -   - |
++---+   +   +   +
+| -   - | -   4 |
++---+---+---+---+
+";
+        assert_eq!(expected, BoardPrinter::new(&board).region_outlines());
+    }
+}
@@ -11,6 +11,8 @@ fn digit_position(d: Digit) -> usize {
 }
 
 #[derive(Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<Digit>", into = "Vec<Digit>"))]
 pub struct DigitSet {
     flags: Flags32,
 }
@@ -84,6 +86,18 @@ impl FromIterator<Digit> for DigitSet {
     }
 }
 
+impl From<Vec<Digit>> for DigitSet {
+    fn from(digits: Vec<Digit>) -> Self {
+        digits.into_iter().collect()
+    }
+}
+
+impl From<DigitSet> for Vec<Digit> {
+    fn from(digits: DigitSet) -> Self {
+        digits.iter().collect()
+    }
+}
+
 impl BitAnd for DigitSet {
     type Output = DigitSet;
 
@@ -111,7 +125,9 @@ pub struct DigitSetIter {
 
 impl DigitSetIter {
     pub fn new(set: DigitSet) -> Self {
-        let iter = Digit::all_digits_iter();
+        // 集合に含まれる最大の数字までを調べる
+        let size = set.flags.last_set().map_or(0, |pos| pos + 1);
+        let iter = Digit::range_iter(size);
         DigitSetIter { set, iter }
     }
 }
@@ -120,13 +136,13 @@ impl Iterator for DigitSetIter {
     type Item = Digit;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(d) = self.iter.next() {
-            if self.set.contains(d) {
-                return Some(d);
-            }
+        if self.set.is_empty() {
+            return None;
         }
-
-        None
+        let set = self.set;
+        let digit = self.iter.find(|d| set.contains(*d))?;
+        self.set.remove(digit);
+        Some(digit)
     }
 }
 
@@ -172,4 +188,33 @@ mod tests {
             assert!(s.contains(Digit::from(i)));
         }
     }
+
+    #[test]
+    fn test_iter() {
+        let s = [25, 3, 16]
+            .iter()
+            .map(|&d| Digit::from(d))
+            .collect::<DigitSet>();
+        assert_eq!(
+            vec![3, 16, 25],
+            s.iter().map(|d| d.get()).collect::<Vec<_>>()
+        );
+        assert_eq!(0, DigitSet::default().iter().count());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let s = [1, 5, 9]
+            .iter()
+            .map(|&d| Digit::from(d))
+            .collect::<DigitSet>();
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!("[1,5,9]", json);
+        assert_eq!(s, serde_json::from_str::<DigitSet>(&json).unwrap());
+
+        // 範囲外の数字は受け付けない
+        assert!(serde_json::from_str::<DigitSet>("[0]").is_err());
+        assert!(serde_json::from_str::<DigitSet>("[26]").is_err());
+    }
 }
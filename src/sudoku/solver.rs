@@ -1,24 +1,25 @@
 use std::cell::{Ref, RefCell, RefMut};
-use std::collections::VecDeque;
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
 
+use itertools::Itertools;
 use thiserror::Error;
 
-use crate::sudoku::board::{Board, BoardError};
+use crate::sudoku::board::{Board, BoardError, ItemPosition};
 use crate::sudoku::candidate::Candidate;
-use crate::sudoku::event::EventQueue;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::{Event, EventQueue};
 use crate::sudoku::filter::{
-    FilterCandidates, FilterContext, FilterInput, HiddenPair, HiddenQuad, HiddenTriple,
-    LockedCandidateClaiming, LockedCandidatePointing, NakedSingle, SingleCandidate,
+    AlternatingInferenceChain, CageCombination, FilterCandidates, FilterContext, FilterInput, Fish,
+    HiddenPair, HiddenQuad, HiddenTriple, LockedCandidateClaiming, LockedCandidatePointing,
+    NakedPair, NakedQuad, NakedSingle, NakedTriple, NamedFilter, RuleOf45, SimpleColoring,
+    SingleCandidate, WWing, XChain, XYChain, XYWing, XYZWing,
 };
+use crate::sudoku::observer::SolverObserver;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::step::{Hint, Step};
 use crate::sudoku::Square;
 
-pub fn add_filter<F: FilterCandidates + 'static>(
-    filters: &mut Vec<Box<dyn FilterCandidates>>,
-    f: F,
-) {
-    filters.push(Box::new(f));
-}
-
 #[derive(Debug, Error)]
 pub enum SolverError {
     #[error("board error")]
@@ -26,85 +27,512 @@ pub enum SolverError {
         #[from]
         source: BoardError,
     },
+    // (幅, 高さ) が盤面と候補で一致しない
+    #[error("size mismatch. board:{board:?}, candidates:{candidates:?}")]
+    SizeMismatch {
+        board: (usize, usize),
+        candidates: (usize, usize),
+    },
+    #[error("unknown filter. name:{0}")]
+    UnknownFilter(String),
 }
 
+// フィルタの使用回数は状態ごとに持ち、後戻りで捨てた仮置きの分は数えない
 #[derive(Clone)]
 struct State {
     board: RefCell<Board<Square>>,
     candidates: RefCell<Board<Candidate>>,
+    filters: RefCell<BTreeMap<&'static str, FilterUsage>>,
+}
+
+impl State {
+    fn new(board: Board<Square>, candidates: Board<Candidate>) -> Self {
+        State {
+            board: RefCell::new(board),
+            candidates: RefCell::new(candidates),
+            filters: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    // 使用回数のフィルタ名は、復元したフィルタの名前に置き換える
+    fn restore(snapshot: StateSnapshot, filters: &[&'static str]) -> Self {
+        let state = State::new(snapshot.board, snapshot.candidates);
+        *state.filters.borrow_mut() = snapshot
+            .filters
+            .into_iter()
+            .filter_map(|(name, usage)| {
+                let filter = filters.iter().find(|filter| **filter == name)?;
+                Some((*filter, usage))
+            })
+            .collect();
+        state
+    }
+
+    fn record_filter(&self, filter: &dyn NamedFilter) {
+        let mut filters = self.filters.borrow_mut();
+        let usage = filters.entry(filter.name()).or_insert(FilterUsage {
+            count: 0,
+            difficulty: filter.difficulty(),
+        });
+        usage.count += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateSnapshot {
+    pub board: Board<Square>,
+    pub candidates: Board<Candidate>,
+    // Statistics のフィルタ名は &'static str なので、保存用には String で持つ
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub filters: BTreeMap<String, FilterUsage>,
+}
+
+impl From<&State> for StateSnapshot {
+    fn from(state: &State) -> Self {
+        StateSnapshot {
+            board: state.board.borrow().clone(),
+            candidates: state.candidates.borrow().clone(),
+            filters: state
+                .filters
+                .borrow()
+                .iter()
+                .map(|(name, usage)| (name.to_string(), *usage))
+                .collect(),
+        }
+    }
+}
+
+// 途中経過の保存用、仮置きで未探索の状態(最後の要素から試す)も含める
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolverSnapshot {
+    pub current: StateSnapshot,
+    pub possible_states: Vec<StateSnapshot>,
+    pub filters: Vec<String>,
+    pub backtracking: bool,
+    pub statistics: StatisticsSnapshot,
+}
+
+// フィルタの使用回数は各 StateSnapshot の方に持つ
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatisticsSnapshot {
+    pub get_stuck: usize,
+    pub back_tracked: usize,
+}
+
+impl From<&Statistics> for StatisticsSnapshot {
+    fn from(statistics: &Statistics) -> Self {
+        StatisticsSnapshot {
+            get_stuck: statistics.get_stuck,
+            back_tracked: statistics.back_tracked,
+        }
+    }
+}
+
+// update で何が起きたか、placed はその更新で確定したセル
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepOutcome {
+    Deduced {
+        filter: &'static str,
+        placed: Vec<(ItemPosition, Digit)>,
+    },
+    // フィルタで進めなくなったので pos に仮置きした
+    Guessed {
+        pos: ItemPosition,
+        placed: Vec<(ItemPosition, Digit)>,
+    },
+    // 矛盾したので、直前の仮置きの残りの候補に戻った
+    Backtracked {
+        placed: Vec<(ItemPosition, Digit)>,
+    },
+    Solved,
+    // 仮置きしない設定で、フィルタでは進めない
+    Stuck,
+    // 矛盾して、試す候補も残っていない
+    Unsolvable,
+}
+
+impl StepOutcome {
+    pub fn is_progress(&self) -> bool {
+        match self {
+            StepOutcome::Deduced { .. }
+            | StepOutcome::Guessed { .. }
+            | StepOutcome::Backtracked { .. } => true,
+            StepOutcome::Solved | StepOutcome::Stuck | StepOutcome::Unsolvable => false,
+        }
+    }
+
+    pub fn placed(&self) -> &[(ItemPosition, Digit)] {
+        match self {
+            StepOutcome::Deduced { placed, .. }
+            | StepOutcome::Guessed { placed, .. }
+            | StepOutcome::Backtracked { placed } => placed,
+            StepOutcome::Solved | StepOutcome::Stuck | StepOutcome::Unsolvable => &[],
+        }
+    }
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+// solve の結果、outcome は止まった理由(Solved, Stuck, Unsolvable のいずれか)
+#[derive(Debug, Clone)]
+pub struct SolveReport {
+    pub outcome: StepOutcome,
+    pub updates: usize,
+    pub statistics: Statistics,
+}
+
+impl SolveReport {
+    pub fn is_solved(&self) -> bool {
+        self.outcome == StepOutcome::Solved
+    }
+}
+
+// 仮置きが必要になった場合の難易度、どのフィルタよりも難しい扱いにする
+const GUESS_DIFFICULTY: f32 = 10.0;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterUsage {
+    pub count: usize,
+    pub difficulty: f32,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Statistics {
     pub get_stuck: usize,
     pub back_tracked: usize,
+    pub filters: BTreeMap<&'static str, FilterUsage>,
+}
+
+impl Statistics {
+    pub fn hardest_filter(&self) -> Option<&'static str> {
+        self.filters
+            .iter()
+            .max_by(|(_, a), (_, b)| a.difficulty.total_cmp(&b.difficulty))
+            .map(|(name, _)| *name)
+    }
+
+    // SE 方式: 必要になった中で最も難しいテクニックの難易度
+    pub fn difficulty(&self) -> f32 {
+        let hardest = self
+            .filters
+            .values()
+            .map(|usage| usage.difficulty)
+            .fold(0.0, f32::max);
+        if self.get_stuck > 0 {
+            hardest.max(GUESS_DIFFICULTY)
+        } else {
+            hardest
+        }
+    }
+
+    // HoDoKu 方式: テクニックごとの難易度 × 使用回数の合計
+    pub fn score(&self) -> f32 {
+        let filters = self
+            .filters
+            .values()
+            .map(|usage| usage.difficulty * usage.count as f32)
+            .sum::<f32>();
+        filters + GUESS_DIFFICULTY * (self.get_stuck + self.back_tracked) as f32
+    }
+}
+
+// フィルタの選択・順序と、フィルタで解けなくなった場合に仮置きするかどうかを指定する
+pub struct SolverBuilder {
+    filters: Vec<Box<dyn FilterCandidates + Send>>,
+    backtracking: bool,
+}
+
+impl Default for SolverBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SolverBuilder {
+    // 易しい順に試す、必要以上に難しい手法で難易度を付けないように difficulty の昇順に並べる
+    pub fn new() -> Self {
+        Self::empty()
+            .filter(NakedSingle)
+            .filter(SingleCandidate)
+            .filter(CageCombination)
+            .filter(RuleOf45)
+            .filter(LockedCandidatePointing)
+            .filter(LockedCandidateClaiming)
+            .filter(NakedPair)
+            .filter(Fish::x_wing())
+            .filter(HiddenPair)
+            .filter(Fish::finned_x_wing())
+            .filter(NakedTriple)
+            .filter(Fish::swordfish())
+            .filter(HiddenTriple)
+            .filter(Fish::finned_swordfish())
+            .filter(XYWing)
+            .filter(XYZWing)
+            .filter(WWing)
+            .filter(SimpleColoring)
+            .filter(NakedQuad)
+            .filter(Fish::jellyfish())
+            .filter(HiddenQuad)
+            .filter(Fish::finned_jellyfish())
+            .filter(XChain)
+            .filter(XYChain)
+            .filter(AlternatingInferenceChain)
+    }
+
+    // フィルタを1つも持たない状態から組み立てる
+    pub fn empty() -> Self {
+        SolverBuilder {
+            filters: Vec::new(),
+            backtracking: true,
+        }
+    }
+
+    // 末尾に追加する、フィルタは追加した順に試す
+    // Solver を他のスレッドに渡せるように Send を要求する
+    pub fn filter<F: FilterCandidates + Send + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn disable(mut self, name: &str) -> Self {
+        self.filters.retain(|f| f.name() != name);
+        self
+    }
+
+    // 指定した名前のフィルタだけを、指定した順に残す
+    pub fn only(mut self, names: &[&str]) -> Self {
+        let mut filters = std::mem::take(&mut self.filters);
+        for name in names {
+            if let Some(i) = filters.iter().position(|f| f.name() == *name) {
+                self.filters.push(filters.remove(i));
+            }
+        }
+        self
+    }
+
+    // false にするとフィルタだけで解けるところまでで止める
+    pub fn backtracking(mut self, backtracking: bool) -> Self {
+        self.backtracking = backtracking;
+        self
+    }
+
+    pub fn filter_names(&self) -> Vec<&'static str> {
+        self.filters.iter().map(|f| f.name()).collect()
+    }
+
+    // 保存したフィルタの構成と統計を引き継いで再開する、フィルタは名前でこのビルダーから選ぶ
+    // このビルダーにないフィルタ名があれば、別の構成で解くことになるのでエラーにする
+    pub fn build_from_snapshot(self, snapshot: SolverSnapshot) -> Result<Solver, SolverError> {
+        let known = self.filter_names();
+        let mut names = snapshot
+            .possible_states
+            .iter()
+            .chain(Some(&snapshot.current))
+            .flat_map(|state| state.filters.keys())
+            .chain(snapshot.filters.iter());
+        if let Some(name) = names.find(|name| !known.contains(&name.as_str())) {
+            return Err(SolverError::UnknownFilter(name.clone()));
+        }
+
+        let board = &snapshot.current.board;
+        for state in snapshot
+            .possible_states
+            .iter()
+            .chain(Some(&snapshot.current))
+        {
+            let size = (state.board.width(), state.board.height());
+            let candidates = (state.candidates.width(), state.candidates.height());
+            if size != (board.width(), board.height()) || size != candidates {
+                return Err(SolverError::SizeMismatch {
+                    board: size,
+                    candidates,
+                });
+            }
+        }
+
+        let names = snapshot.filters.iter().map(|s| s.as_str()).collect_vec();
+        let mut solver = self
+            .only(&names)
+            .backtracking(snapshot.backtracking)
+            .build(board.clone());
+
+        let restore = |state| State::restore(state, &known);
+        solver.current_state = State::restore(snapshot.current, &known);
+        solver.possible_states =
+            RefCell::new(snapshot.possible_states.into_iter().map(restore).collect());
+        solver.statistics = Statistics {
+            get_stuck: snapshot.statistics.get_stuck,
+            back_tracked: snapshot.statistics.back_tracked,
+            filters: BTreeMap::new(),
+        };
+        Ok(solver)
+    }
+
+    pub fn build(self, board: Board<Square>) -> Solver {
+        let items = board
+            .items()
+            .map(|sq| Candidate::new(sq, board.width()))
+            .collect();
+        let candidates = board.with_items(items);
+
+        Solver {
+            context: RefCell::new(FilterContext::default()),
+            event_queue: RefCell::new(EventQueue::default()),
+            current_state: State::new(board, candidates),
+            possible_states: RefCell::new(Vec::new()),
+            filters: self.filters,
+            backtracking: self.backtracking,
+            statistics: Statistics::default(),
+            steps: RefCell::new(Vec::new()),
+            observers: RefCell::new(Vec::new()),
+        }
+    }
 }
 
 pub struct Solver {
     context: RefCell<FilterContext>,
     event_queue: RefCell<EventQueue>,
     current_state: State,
-    possible_states: RefCell<VecDeque<State>>,
-    stuck_state: Option<State>,
-    filters: Vec<Box<dyn FilterCandidates>>,
+    possible_states: RefCell<Vec<State>>,
+    filters: Vec<Box<dyn FilterCandidates + Send>>,
+    backtracking: bool,
     statistics: Statistics,
+    steps: RefCell<Vec<Step>>,
+    observers: RefCell<Vec<Box<dyn SolverObserver>>>,
 }
 
 impl Solver {
-    fn filters() -> Vec<Box<dyn FilterCandidates>> {
-        let mut filters = Vec::new();
-        add_filter(&mut filters, NakedSingle);
-        add_filter(&mut filters, SingleCandidate);
-        add_filter(&mut filters, LockedCandidatePointing);
-        add_filter(&mut filters, LockedCandidateClaiming);
-        add_filter(&mut filters, HiddenPair);
-        add_filter(&mut filters, HiddenTriple);
-        add_filter(&mut filters, HiddenQuad);
-        filters
+    pub fn new(board: Board<Square>) -> Self {
+        SolverBuilder::new().build(board)
     }
 
-    pub fn new(board: Board<Square>) -> Self {
-        let items = board.items().map(Candidate::new).collect();
-        let candidates = Board::new(items, board.block_size(), board.num_blocks());
-        let filters = Self::filters();
+    pub fn from_candidates(
+        board: Board<Square>,
+        mut candidates: Board<Candidate>,
+    ) -> Result<Self, SolverError> {
+        let size = (board.width(), board.height());
+        if size != (candidates.width(), candidates.height()) {
+            return Err(SolverError::SizeMismatch {
+                board: size,
+                candidates: (candidates.width(), candidates.height()),
+            });
+        }
 
-        Solver {
-            context: RefCell::new(FilterContext::default()),
-            event_queue: RefCell::new(EventQueue::default()),
-            current_state: State {
-                board: RefCell::new(board),
-                candidates: RefCell::new(candidates),
-            },
-            possible_states: RefCell::new(VecDeque::new()),
-            stuck_state: None,
-            filters,
-            statistics: Statistics::default(),
+        // 確定済みのセルは候補を持たない
+        for pos in board.item_positions() {
+            if board.item_at(pos).is_fixed() {
+                candidates.item_at_mut(pos).clear();
+            }
+        }
+
+        // 追加のユニットは盤面の方から引き継ぐ
+        let candidates = board.with_items(candidates.items().cloned().collect());
+        let solver = Self::new(board);
+        *solver.candidates_mut() = candidates;
+        solver.update_board()?;
+        Ok(solver)
+    }
+
+    pub fn from_snapshot(snapshot: SolverSnapshot) -> Result<Self, SolverError> {
+        SolverBuilder::new().build_from_snapshot(snapshot)
+    }
+
+    pub fn snapshot(&self) -> SolverSnapshot {
+        SolverSnapshot {
+            current: StateSnapshot::from(&self.current_state),
+            possible_states: self
+                .possible_states
+                .borrow()
+                .iter()
+                .map(StateSnapshot::from)
+                .collect(),
+            filters: self
+                .filters
+                .iter()
+                .map(|filter| filter.name().to_string())
+                .collect(),
+            backtracking: self.backtracking,
+            statistics: StatisticsSnapshot::from(&self.statistics),
         }
     }
 
-    pub fn board(&self) -> Ref<Board<Square>> {
+    pub fn board(&self) -> Ref<'_, Board<Square>> {
         self.current_state.board.borrow()
     }
 
-    pub fn board_mut(&self) -> RefMut<Board<Square>> {
+    pub fn board_mut(&self) -> RefMut<'_, Board<Square>> {
         self.current_state.board.borrow_mut()
     }
 
-    pub fn candidates(&self) -> Ref<Board<Candidate>> {
+    pub fn candidates(&self) -> Ref<'_, Board<Candidate>> {
         self.current_state.candidates.borrow()
     }
 
-    fn candidates_mut(&self) -> RefMut<Board<Candidate>> {
+    fn candidates_mut(&self) -> RefMut<'_, Board<Candidate>> {
         self.current_state.candidates.borrow_mut()
     }
 
+    // 登録した順に通知する
+    pub fn add_observer<O: SolverObserver + 'static>(&mut self, observer: O) {
+        self.observers.get_mut().push(Box::new(observer));
+    }
+
+    fn notify(&self, mut f: impl FnMut(&mut dyn SolverObserver)) {
+        for observer in self.observers.borrow_mut().iter_mut() {
+            f(observer.as_mut());
+        }
+    }
+
+    // フィルタの使用回数は、今たどっている状態に至るまでの分
     pub fn statistics(&self) -> Statistics {
-        self.statistics
+        Statistics {
+            filters: self.current_state.filters.borrow().clone(),
+            ..self.statistics.clone()
+        }
+    }
+
+    pub fn steps(&self) -> Ref<'_, Vec<Step>> {
+        self.steps.borrow()
+    }
+
+    pub fn next_hint(&self) -> Option<Hint> {
+        // 易しいフィルタから現在の候補の複製へ適用し、最初に進展のあったものを返す
+        // 並び順を変えたビルダーでも易しい順に試す、同じ難易度なら並び順で先のもの
+        let filters = self
+            .filters
+            .iter()
+            .sorted_by(|a, b| a.difficulty().total_cmp(&b.difficulty()));
+        let mut event_queue = EventQueue::default();
+        filters.into_iter().find_map(|filter| {
+            let mut candidates = self.candidates().clone();
+            event_queue.set_filter(filter.name());
+            filter.filter_candidates(FilterInput::new(
+                &mut self.context.borrow_mut(),
+                &mut event_queue,
+                &self.board(),
+                &self.candidates(),
+            ));
+
+            let steps = Self::apply_events(&mut event_queue, &mut candidates, |_, _| {});
+            if steps.is_empty() {
+                None
+            } else {
+                Some(Hint::new(filter.as_ref(), steps, &candidates))
+            }
+        })
     }
 
-    pub fn update(&mut self) -> Result<bool, SolverError> {
-        let mut evaluated = false;
+    pub fn update(&mut self) -> StepOutcome {
+        if self.board().is_complete() {
+            return StepOutcome::Solved;
+        }
+
+        let mut fired = None;
         for filter in self.filters.iter() {
+            self.notify(|o| o.on_filter_start(filter.name()));
+            self.event_queue.borrow_mut().set_filter(filter.name());
             filter.filter_candidates(FilterInput::new(
                 &mut self.context.borrow_mut(),
                 &mut self.event_queue.borrow_mut(),
@@ -112,87 +540,1088 @@ impl Solver {
                 &self.candidates(),
             ));
 
-            evaluated = self.evaluate_events();
+            let evaluated = self.evaluate_events();
+            self.notify(|o| o.on_filter_end(filter.name(), evaluated));
             if evaluated {
+                self.current_state.record_filter(filter.as_ref());
+                fired = Some(filter.name());
                 break;
             }
         }
 
-        if evaluated {
-            if let Err(err) = self.update_board() {
-                // 更新中にエラーが発生した場合は他のパターンがあれば試す、なければ断念
-                if self.possible_states.borrow().is_empty() {
-                    Err(err)?;
-                }
-            }
+        if let Some(filter) = fired {
+            return match self.update_board() {
+                Ok(placed) => StepOutcome::Deduced { filter, placed },
+                // 仮置きが誤っていた場合は次の候補を試す、なければ解なし
+                Err(_) => self.backtrack(),
+            };
+        }
+
+        let cell = self.most_constrained_cell();
+        if !self.backtracking {
+            // 候補のなくなったセルがあれば、仮置きしなくても解なしと分かる
+            return match cell {
+                Some((_, digits)) if digits.is_empty() => StepOutcome::Unsolvable,
+                _ => StepOutcome::Stuck,
+            };
         }
 
-        if !evaluated && !self.board().is_complete() {
-            // フィルタで解けなくなった、Grid#7 でだけ発生するはず
-            // - 手詰まりの状態を保存する
-            // - 2択・3択と候補を増やしながらリトライする
-            if self.possible_states.borrow().is_empty() {
+        // フィルタで解けなくなったら、候補の最も少ないセルに仮置きして探索を続ける
+        match cell {
+            Some((pos, digits)) if !digits.is_empty() => {
                 self.statistics.get_stuck += 1;
-                self.update_possibilities();
-            } else {
-                self.statistics.back_tracked += 1;
-                self.current_state = self.possible_states.borrow_mut().pop_front().unwrap();
+                self.guess(pos, digits)
             }
-
-            evaluated = true;
+            // 候補のなくなったセルがある → 直前の仮置きに戻る
+            _ => self.backtrack(),
         }
+    }
 
-        Ok(evaluated)
+    // 進展がなくなるまで update を繰り返す
+    pub fn solve(&mut self) -> SolveReport {
+        let mut updates = 0;
+        loop {
+            let outcome = self.update();
+            if !outcome.is_progress() {
+                return SolveReport {
+                    outcome,
+                    updates,
+                    statistics: self.statistics(),
+                };
+            }
+            updates += 1;
+        }
     }
 
     fn evaluate_events(&self) -> bool {
-        let mut evaluated = false;
-        // println!("########################################################################################## START EVALUATING");
-        while let Some(mut event) = self.event_queue.borrow_mut().pop_front() {
-            // dbg!(event);
-            // self.board().show();
-            // self.candidates().show();
-            evaluated |= event.evaluate(&mut self.candidates_mut());
-        }
-        // println!("########################################################################################## FINISH EVALUATING");
-        // self.board().show();
-        // self.candidates().show();
-        // println!();
+        let steps = Self::apply_events(
+            &mut self.event_queue.borrow_mut(),
+            &mut self.candidates_mut(),
+            |event, changed| self.notify(|o| o.on_event(event, changed)),
+        );
+        let evaluated = !steps.is_empty();
+        self.steps.borrow_mut().extend(steps);
         evaluated
     }
 
-    fn update_board(&self) -> Result<(), BoardError> {
+    fn apply_events(
+        event_queue: &mut EventQueue,
+        candidates: &mut Board<Candidate>,
+        mut on_event: impl FnMut(&Event, bool),
+    ) -> Vec<Step> {
+        let mut steps = Vec::new();
+        while let Some(event) = event_queue.pop_front() {
+            let cells = event.scope().item_positions(candidates);
+            let before = cells
+                .iter()
+                .map(|pos| candidates.item_at(*pos).digits())
+                .collect_vec();
+
+            let changed = event.evaluate(candidates);
+            on_event(&event, changed);
+            if changed {
+                steps.push(Step::new(&event, cells, before, candidates));
+            }
+        }
+        steps
+    }
+
+    fn update_board(&self) -> Result<Vec<(ItemPosition, Digit)>, BoardError> {
+        let mut placed = Vec::new();
         let positions = self.candidates().item_positions();
         for pos in positions {
             if let Some(digit) = self.candidates_mut().take_fixed_digit_at(pos) {
                 self.board_mut().fix_digit_at(pos, digit)?;
+                self.notify(|o| o.on_fix(pos, digit));
+                placed.push((pos, digit));
             }
         }
 
-        Ok(())
+        Ok(placed)
     }
 
-    fn update_possibilities(&mut self) {
-        // 手詰まりの状態を保存する
-        assert!(self.stuck_state.is_none());
-        self.stuck_state = Some(self.current_state.clone());
-
-        // 可能性のある状態を登録する
+    fn most_constrained_cell(&self) -> Option<(ItemPosition, DigitSet)> {
+        let board = self.board();
         let candidates = self.candidates();
-        for pos in candidates.item_positions().filter(|pos| {
-            self.candidates().item_at(*pos).digits().len() == self.statistics.get_stuck + 1
-        }) {
-            let c = candidates.item_at(pos);
-            let possible_states = c.digits().iter().map(|d| {
-                let mut next_candidates = self.candidates().clone();
-                next_candidates.item_at_mut(pos).remove(d);
-
-                State {
-                    board: RefCell::new(self.board().clone()),
-                    candidates: RefCell::new(next_candidates),
+        board
+            .item_positions()
+            .filter(|pos| !board.item_at(*pos).is_fixed())
+            .map(|pos| (pos, candidates.item_at(pos).digits()))
+            .min_by_key(|(_, digits)| digits.len())
+    }
+
+    fn guess(&mut self, pos: ItemPosition, digits: DigitSet) -> StepOutcome {
+        self.notify(|o| o.on_branch(pos, digits));
+
+        // 小さい数字から試すように、逆順にスタックへ積む
+        for d in digits.iter().collect_vec().into_iter().rev() {
+            let mut candidates = self.candidates().clone();
+            *candidates.item_at_mut(pos) =
+                Candidate::with_digits(DigitSet::from_iter(Some(d)), pos.row, pos.col);
+
+            let state = State::new(self.board().clone(), candidates);
+            *state.filters.borrow_mut() = self.current_state.filters.borrow().clone();
+            self.possible_states.borrow_mut().push(state);
+        }
+
+        match self.next_state() {
+            Some(placed) => StepOutcome::Guessed { pos, placed },
+            None => StepOutcome::Unsolvable,
+        }
+    }
+
+    fn backtrack(&mut self) -> StepOutcome {
+        if self.possible_states.borrow().is_empty() {
+            return StepOutcome::Unsolvable;
+        }
+
+        self.statistics.back_tracked += 1;
+        self.notify(|o| o.on_backtrack());
+        match self.next_state() {
+            Some(placed) => StepOutcome::Backtracked { placed },
+            None => StepOutcome::Unsolvable,
+        }
+    }
+
+    // スタックから矛盾のない状態を取り出す、残っていなければ None
+    fn next_state(&mut self) -> Option<Vec<(ItemPosition, Digit)>> {
+        loop {
+            self.current_state = self.possible_states.borrow_mut().pop()?;
+            if let Ok(placed) = self.update_board() {
+                return Some(placed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use itertools::Itertools;
+
+    use crate::sudoku::filter::tests::apply_filter;
+    use crate::sudoku::generator::rate;
+
+    use crate::sudoku::{
+        Board, BoardLoader, BoardWriter, Digit, DigitSet, Event, FilterCandidates, FilterInput,
+        Fish, House, ItemPosition, NakedQuad, NakedSingle, NamedFilter, SingleCandidate, Solver,
+        SolverBuilder, SolverError, SolverObserver, Square, Statistics, StepKind, StepOutcome,
+        UnitKind, DEFAULT_DIFFICULTY,
+    };
+
+    fn load_board(no: usize) -> Board<Square> {
+        let s = include_str!("../../assets/p096_sudoku.txt");
+        BoardLoader::load(s).unwrap().remove(no - 1)
+    }
+
+    fn solver(no: usize) -> Solver {
+        let mut solver = Solver::new(load_board(no));
+        solver.solve();
+        assert!(solver.board().is_complete());
+        solver
+    }
+
+    fn solve(no: usize) -> Statistics {
+        solver(no).statistics()
+    }
+
+    #[test]
+    fn test_difficulty() {
+        // Grid 01 はシングルだけで解ける
+        let easy = solve(1);
+        assert_eq!(Some("NakedSingle"), easy.hardest_filter());
+        assert_eq!(1.0, easy.difficulty());
+
+        // Grid 07 は X-Wing が必要
+        let hard = solve(7);
+        assert_eq!(Some("XWing"), hard.hardest_filter());
+        assert_eq!(3.2, hard.difficulty());
+        assert!(hard.filters["XWing"].count > 0);
+        assert!(easy.score() < hard.score());
+
+        // 途中で Naked Quad と X-Wing のどちらも使える局面があっても、易しい X-Wing で評価する
+        let mut solver = Solver::new(load_board(7));
+        let mut both = false;
+        while solver.update().is_progress() {
+            let board = solver.board().clone();
+            let available = |filter: &dyn FilterCandidates| {
+                apply_filter(filter, &board, &mut solver.candidates().clone())
+            };
+            both |= available(&NakedQuad) && available(&Fish::x_wing());
+        }
+        assert!(both);
+        assert_eq!(3.2, rate(&load_board(7)));
+    }
+
+    #[test]
+    fn test_steps() {
+        let solver = solver(7);
+        let steps = solver.steps();
+        assert_eq!("NakedSingle", steps[0].filter);
+
+        let step = steps.iter().find(|s| s.filter == "XWing").unwrap();
+        assert_eq!(StepKind::Remove, step.kind);
+        assert_eq!(Some(House::Column(5)), step.house);
+        assert_eq!(
+            "XWing {6} at r1c6,r1c9,r9c6,r9c9 removes 6 in column 6 from r7c6",
+            step.to_string()
+        );
+
+        // どのペアが除外したかも表示する
+        let step = steps.iter().find(|s| s.filter == "NakedPair").unwrap();
+        assert_eq!(
+            vec![
+                ItemPosition { row: 7, col: 0 },
+                ItemPosition { row: 7, col: 3 }
+            ],
+            step.pattern
+        );
+        assert_eq!(
+            "NakedPair {1,7} at r8c1,r8c4 removes 1 in row 8 from r8c3,r8c5",
+            step.to_string()
+        );
+
+        // X-Wing を使わなければ Hidden Pair で解く
+        let mut solver = SolverBuilder::new().disable("XWing").build(load_board(7));
+        solver.solve();
+        let steps = solver.steps();
+        let step = steps.iter().find(|s| s.filter == "HiddenPair").unwrap();
+        assert_eq!(Some(House::Column(6)), step.house);
+        assert_eq!(
+            "HiddenPair {4,9} in column 7 at r5c7,r7c7 removes 1,3,5,6",
+            step.to_string()
+        );
+    }
+
+    #[test]
+    fn test_next_hint() {
+        // 解き終わった盤面にはヒントがない
+        assert!(solver(7).next_hint().is_none());
+
+        let mut solver = Solver::new(load_board(7));
+
+        // ヒントの取得では盤面・候補を更新しない
+        let candidates = solver.candidates().clone();
+        let hint = solver.next_hint().unwrap();
+        assert_eq!("NakedSingle", hint.filter);
+        assert!(solver
+            .candidates()
+            .items()
+            .zip(candidates.items())
+            .all(|(a, b)| a.digits() == b.digits()));
+
+        // ヒントと同じ内容が適用される
+        assert!(solver.update().is_progress());
+        assert_eq!(
+            hint.steps.iter().map(|s| s.to_string()).collect_vec(),
+            solver.steps().iter().map(|s| s.to_string()).collect_vec()
+        );
+        for (pos, digit) in hint.placements {
+            assert_eq!(Some(digit), solver.board().item_at(pos).digit());
+        }
+
+        // 難しい手法が先に並んでいても、使える中で最も易しいものをヒントにする
+        let mut solver = SolverBuilder::empty()
+            .filter(SingleCandidate)
+            .filter(NakedSingle)
+            .build(load_board(7));
+        loop {
+            let board = solver.board().clone();
+            let available = |filter: &dyn FilterCandidates| {
+                apply_filter(filter, &board, &mut solver.candidates().clone())
+            };
+            if available(&SingleCandidate) && available(&NakedSingle) {
+                break;
+            }
+            assert!(solver.update().is_progress());
+        }
+        let hint = solver.next_hint().unwrap();
+        assert_eq!("NakedSingle", hint.filter);
+        assert_eq!(1.0, hint.difficulty);
+        assert!(hint.steps.iter().all(|s| s.filter == "NakedSingle"));
+
+        // 外部のフィルタの難易度が NaN でもパニックしない
+        struct Unrated;
+
+        impl NamedFilter for Unrated {
+            fn name(&self) -> &'static str {
+                "Unrated"
+            }
+
+            fn difficulty(&self) -> f32 {
+                f32::NAN
+            }
+        }
+
+        impl FilterCandidates for Unrated {
+            fn filter_candidates(&self, _input: FilterInput) {}
+        }
+
+        let solver = SolverBuilder::new().filter(Unrated).build(load_board(7));
+        assert_eq!("NakedSingle", solver.next_hint().unwrap().filter);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_snapshot() {
+        let mut partial = Solver::new(load_board(7));
+        for _ in 0..10 {
+            assert!(partial.update().is_progress());
+        }
+
+        // JSON を経由して復元しても、同じ解にたどり着く
+        let json = serde_json::to_string(&partial.snapshot()).unwrap();
+        let mut resumed = Solver::from_snapshot(serde_json::from_str(&json).unwrap()).unwrap();
+        assert!(resumed.board().items().eq(partial.board().items()));
+
+        resumed.solve();
+        assert!(resumed.board().items().eq(solver(7).board().items()));
+    }
+
+    #[test]
+    fn test_snapshot_filters() {
+        // フィルタの構成・仮置きの有無・使用回数も引き継ぐ
+        let mut partial = SolverBuilder::new()
+            .disable("XWing")
+            .backtracking(false)
+            .build(load_board(7));
+        for _ in 0..10 {
+            assert!(partial.update().is_progress());
+        }
+        let mut resumed = Solver::from_snapshot(partial.snapshot()).unwrap();
+        let snapshot = resumed.snapshot();
+        assert!(!snapshot.backtracking);
+        assert!(!snapshot.filters.iter().any(|name| name == "XWing"));
+        assert_eq!(partial.snapshot().filters, snapshot.filters);
+
+        let before = partial.statistics();
+        let after = resumed.statistics();
+        assert_eq!(
+            before.filters.keys().collect_vec(),
+            after.filters.keys().collect_vec()
+        );
+        assert!(before
+            .filters
+            .iter()
+            .all(|(name, usage)| after.filters[name].count == usage.count));
+
+        resumed.solve();
+        assert!(resumed.board().is_complete());
+        let after = resumed.statistics();
+        assert!(!after.filters.contains_key("XWing"));
+        assert!(after.filters["NakedSingle"].count > before.filters["NakedSingle"].count);
+
+        // 知らないフィルタ名が含まれていれば、黙って落とさずにエラーにする
+        let mut snapshot = partial.snapshot();
+        snapshot.filters.push("FullHouse".to_string());
+        assert!(matches!(
+            Solver::from_snapshot(snapshot),
+            Err(SolverError::UnknownFilter(name)) if name == "FullHouse"
+        ));
+
+        let mut snapshot = partial.snapshot();
+        let usage = snapshot.current.filters["NakedSingle"];
+        snapshot
+            .current
+            .filters
+            .insert("FullHouse".to_string(), usage);
+        assert!(matches!(
+            Solver::from_snapshot(snapshot),
+            Err(SolverError::UnknownFilter(name)) if name == "FullHouse"
+        ));
+
+        // ビルダーが知っていれば復元できる
+        let mut snapshot = partial.snapshot();
+        snapshot.filters.push("FullHouse".to_string());
+        let resumed = SolverBuilder::new()
+            .filter(FullHouse)
+            .build_from_snapshot(snapshot)
+            .unwrap();
+        assert_eq!(
+            Some("FullHouse"),
+            resumed.snapshot().filters.last().map(|s| s.as_str())
+        );
+    }
+
+    #[test]
+    fn test_from_candidates() {
+        let mut partial = Solver::new(load_board(7));
+        for _ in 0..10 {
+            assert!(partial.update().is_progress());
+        }
+
+        // 候補グリッドを経由して途中から解き直す
+        let s = BoardWriter::write_pencil_marks(&partial.board(), &partial.candidates());
+        let candidates = BoardLoader::load_pencil_marks(&s).unwrap();
+        let mut resumed = Solver::from_candidates(candidates.squares(), candidates).unwrap();
+        assert!(resumed.board().items().eq(partial.board().items()));
+
+        resumed.solve();
+        assert!(resumed.board().items().eq(solver(7).board().items()));
+
+        // 盤面と候補の大きさが違えばエラーにする
+        let lines = ["000406", "000020", "230000", "500001", "300005", "040000"];
+        let small = Solver::new(BoardLoader::from_lines(lines.iter().copied()).unwrap());
+        let result = Solver::from_candidates(load_board(7), small.candidates().clone());
+        assert!(matches!(
+            result,
+            Err(SolverError::SizeMismatch {
+                board: (9, 9),
+                candidates: (6, 6),
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rectangular_blocks() {
+        let puzzles = [
+            (
+                vec!["000406", "000020", "230000", "500001", "300005", "040000"],
+                "123456456123231564564231312645645312",
+            ),
+            (
+                vec![
+                    "00040670", "00001000", "00050700", "00802000", "00000002", "70000450",
+                    "00600100", "80230000",
+                ],
+                "1234567856781234234567816781234534567812781234564567812381234567",
+            ),
+        ];
+
+        for (lines, expected) in puzzles.iter() {
+            let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+            let mut solver = Solver::new(board);
+            solver.solve();
+            assert!(solver.board().is_complete());
+
+            let digits = solver
+                .board()
+                .items()
+                .map(|sq| sq.digit().unwrap().get().to_string())
+                .join("");
+            assert_eq!(*expected, digits);
+        }
+    }
+
+    #[test]
+    fn test_large_boards() {
+        let puzzles = [
+            (
+                vec![
+                    "...7B...C9.A",
+                    ".....5.1..2.",
+                    "..83C.....5.",
+                    ".A5...1.....",
+                    "B..8..9.7...",
+                    "4.2...86A.C.",
+                    "..........3.",
+                    "..B283....A.",
+                    ".8.69..41..2",
+                    "1..B6....7..",
+                    "..A......3..",
+                    "95......6.8C",
+                ],
+                "5417B238C96A6C9A4571B8232B83C6A94157CA59741236B8B368AC95724147213B86A5C9A945172B8C3671B2836C94A538C69A541B72123B68CA579486AC5947231B957421B36A8C",
+            ),
+            (
+                vec![
+                    ".8.7.G.C.1.....D",
+                    ".....F9.3.GC...1",
+                    "56...B..7.....3.",
+                    "G.2.A..6...E....",
+                    "39F.....6......B",
+                    "415....D8.3.A.C.",
+                    "A..C6..1.B..3..F",
+                    ".......9.G.2...5",
+                    "E.4...7B93.F..2.",
+                    ".B...C..2.6.E514",
+                    ".F39........8..7",
+                    "..A2..45.7..C..3",
+                    "...B...3.6...4..",
+                    "2.C.G.........B8",
+                    "D..5.98...231AG6",
+                    ".A.G5.E...9.2.F.",
+                ],
+                "F8973G2CA156BE4DBED47F9832GC56A1561A4BDE79F8GC32GC23A5164DBEF87939F8CAG265417DEB4156E7BD8F39A2CGA2GC6451EB7D398F7DBE83F9CGA24165E541D87B93CF6G2A8B7D9C3F2A6GE514CF3926AG14E58BD76GA21E45D78BCF93978BF2C3G61AD45E23CFG16A5ED497B8D4E5B987FC231AG61A6G5DE4B89723FC",
+            ),
+            (
+                vec![
+                    "7H9...B1...OA..2.M5C.G8D.",
+                    "..8E....7..K13FO.6IP2MC..",
+                    ".A......5..E...NH.7..3.F1",
+                    ".1..36..I.C2...EJ.D8.L...",
+                    ".4C..G.JD.9NH..K.3.BO...A",
+                    "3.F.1A.2.P5.E..8.JGD9H7L.",
+                    "L.79H1.O3......CE.M5.J.G.",
+                    "GND....K.9FBO......IC..ME",
+                    "ME..4.DN..7......1.FP...2",
+                    "..IPA.5.MCD.N....HL7B1.3.",
+                    "AC.I2.M845G.9.J.B.HLFO..P",
+                    "1P3FO26...M..........K..B",
+                    "H.L..O3...6.C...8..MDNGJ.",
+                    "48..E.G..DL7B.HF..1..26..",
+                    ".9G.NK..H73F.O.IC.A.5..48",
+                    "O..3PC...6.M...G79N..BH..",
+                    ".7.....FK...IP.6..2A.....",
+                    "ED4M.9J7N.HL.B.3IP..6C...",
+                    "25A..84DE.....N....H...O.",
+                    ".F.L.P.....6....D8...9JN7",
+                    ".3.HFIO..12.M....D.E..N.L",
+                    "8.E.D.....KH..B...P.A52CM",
+                    ".6..I.2.......8J.7..H.K..",
+                    "...........JL.....B...OP6",
+                    "9.N.7......16IPAM.C.4D.8G",
+                ],
+                "7H9NL3B1FKPOA6I24M5CEG8DJDJ8EGL9H7NBK13FOA6IP2MC54IAPO6MC4528EJGDNHL79K3BF1F1BK36PAIOC24M5EJGD8NL97H54C2MG8JDE9NHL7K13FBO6PIA3OFB1AI26P5CE4M8NJGD9H7LKLK79H1FO3BIP2A6CE4M58JDGNGND8JH7KL9FBO13P2A6IC45MEME5C4JDNG879KHLBO13FPAI6262IPA45EMCD8NJG9KHL7B1F3OAC6I2EM845GD9NJ7BKHLFO31P1P3FO26CAIM58E4D9NJG7KLHBHBL7KO3P1F6IC2A58E4MDNGJ948M5ENG9JDL7BKHFPO13I26ACJ9GDNKLBH73FPO1IC2A65EM48OI13PCA5264MD8EG79NJLBHKFN7JG9BHFKL13IPO65C2AM84EDED4M89J7NGHLFBK3IPO16CA2525A6C84DEMJG79NLFBKH3P1OIKFHLBP1IO3A65C2MD8E4G9JN7B3KHFIO6P12AM5C4GD8EJ7N9L8GE4D7NL9JKH3FB16IPOA52CMP6O1I52MCAE4GD8JL79NHFKB3CM2A5DEG84NJL79H3FBK1IOP69LNJ7FK3BHO16IPAM5C24DE8G",
+            ),
+        ];
+
+        for (lines, expected) in puzzles.iter() {
+            let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+            let mut solver = Solver::new(board);
+            solver.solve();
+            assert!(solver.board().is_complete());
+
+            let digits = solver
+                .board()
+                .items()
+                .map(|sq| char::from(sq.digit().unwrap()))
+                .collect::<String>();
+            assert_eq!(*expected, digits);
+        }
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let lines = [
+            "000456080",
+            "000000000",
+            "000003400",
+            "601007030",
+            "200000004",
+            "008604000",
+            "300900000",
+            "000000260",
+            "002001007",
+        ];
+        let mut board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        assert!(!board.has_unique_solution());
+        board.add_diagonals();
+        assert!(board.has_unique_solution());
+
+        let mut solver = Solver::new(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert!(solver.board().validate().is_ok());
+
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| char::from(sq.digit().unwrap()))
+            .collect::<String>();
+        assert_eq!(
+            "123456789456789123789123456641297538297835614538614972375962841914378265862541397",
+            digits
+        );
+        assert!(solver
+            .steps()
+            .iter()
+            .any(|step| matches!(step.house, Some(House::Unit(_)))));
+    }
+
+    #[test]
+    fn test_killer() {
+        let s = "aabbccdd.
+eeffgghh.
+iijjkkll.
+mmnnoopp.
+qqrrsstt.
+uuvvwwxx.
+yyzzAABB.
+CCDDEEFF.
+GGHHIIJJ.
+a=12 b=12 c=3 d=11 e=15 f=10 g=9 h=10 i=7 j=9 k=13 l=13
+m=9 n=9 o=5 p=16 q=9 r=14 s=10 t=4 u=4 v=13 w=17 x=6
+y=10 z=8 A=17 B=6 C=9 D=6 E=8 F=13 G=15 H=9 I=8 J=11
+";
+        let mut board = BoardLoader::load_killer(s).unwrap();
+        assert_eq!(36, board.cages().len());
+
+        // ケージだけでは解が定まらないので、いくつか数字を置く
+        for (row, col, d) in [(1, 0, 9), (6, 5, 9), (7, 0, 8), (8, 4, 1), (8, 6, 3)].iter() {
+            board
+                .fix_digit_at(
+                    ItemPosition {
+                        row: *row,
+                        col: *col,
+                    },
+                    Digit::from(*d),
+                )
+                .unwrap();
+        }
+        let mut solver = Solver::new(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert!(solver.board().validate().is_ok());
+
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| char::from(sq.digit().unwrap()))
+            .collect::<String>();
+        assert_eq!(
+            "483921657967345821251876493548132976729564138136798245372689514814253769695417382",
+            digits
+        );
+        assert!(solver.statistics().filters.contains_key("CageCombination"));
+    }
+
+    #[test]
+    fn test_jigsaw() {
+        let s = "000050700
+000010020
+000109000
+009000030
+000200060
+050800002
+000601000
+000000040
+000000000
+
+aabbbbbcf
+aaaabcccf
+daaebbcff
+ddaebeccf
+dddeeecff
+gddeeecif
+gdghhhiif
+gggghhhii
+gghhhiiii
+";
+        let board = BoardLoader::load_jigsaw(s).unwrap();
+        assert!(board.has_unique_solution());
+
+        let mut solver = Solver::new(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert!(solver.board().validate().is_ok());
+
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| char::from(sq.digit().unwrap()))
+            .collect::<String>();
+        assert_eq!(
+            "123456789364517928678129453249785631831294567457863192592631874916378245785942316",
+            digits
+        );
+        let statistics = solver.statistics();
+        assert!(statistics.filters.contains_key("LockedCandidate(Pointing)"));
+        assert!(statistics.filters.contains_key("FinnedXWing"));
+
+        // 長方形のブロックに分けられない 5x5 でも、領域を指定すれば解ける
+        let s = "00300
+00500
+05000
+20150
+00000
+
+aaabb
+acabb
+cccbd
+ceedd
+eeedd
+";
+        let board = BoardLoader::load_jigsaw(s).unwrap();
+        assert_eq!(5, board.width());
+        assert!(board.has_unique_solution());
+
+        let mut solver = Solver::new(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert!(solver.board().validate().is_ok());
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| char::from(sq.digit().unwrap()))
+            .collect::<String>();
+        assert_eq!("1234541523354122315454231", digits);
+    }
+
+    #[test]
+    fn test_windows() {
+        let lines = [
+            "000050700",
+            "000093050",
+            "000200000",
+            "000000090",
+            "000605000",
+            "010300002",
+            "002001000",
+            "000800030",
+            "000030000",
+        ];
+        let mut board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        board.add_windows();
+        assert!(board.has_unique_solution());
+
+        let mut solver = Solver::new(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert!(solver.board().validate().is_ok());
+
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| char::from(sq.digit().unwrap()))
+            .collect::<String>();
+        assert_eq!(
+            "123456789768193254549287163235718496497625318816349572372961845954872631681534927",
+            digits
+        );
+        // 窓と行・列・ブロックの交差から候補を除外している
+        assert!(solver.steps().iter().any(|step| {
+            step.filter.starts_with("LockedCandidate")
+                && matches!(step.house, Some(House::Unit(UnitKind::Window)))
+        }));
+    }
+
+    // 行の中で1つだけ空いているセルに残りの数字を入れる、外部のフィルタの例
+    struct FullHouse;
+
+    impl NamedFilter for FullHouse {
+        fn name(&self) -> &'static str {
+            "FullHouse"
+        }
+    }
+
+    impl FilterCandidates for FullHouse {
+        fn filter_candidates(&self, mut input: FilterInput) {
+            let board = input.board().clone();
+            for row in board.each_rows() {
+                let (empty, fixed): (Vec<_>, Vec<_>) = board
+                    .each_columns()
+                    .map(|col| ItemPosition { row, col })
+                    .partition(|pos| board.item_at(*pos).digit().is_none());
+                if empty.len() != 1 {
+                    continue;
                 }
-            });
-            self.possible_states.borrow_mut().extend(possible_states);
+
+                let mut digits = Digit::range_iter(board.width()).collect::<DigitSet>();
+                for pos in fixed {
+                    digits.remove(board.item_at(pos).digit().unwrap());
+                }
+                input.retain_candidates(digits, empty);
+            }
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Solver>();
+
+        // 途中まで解いた Solver を別スレッドに渡して続きを解く
+        let mut solver = Solver::new(load_board(7));
+        solver.update();
+        let solver = std::thread::spawn(move || {
+            solver.solve();
+            solver
+        })
+        .join()
+        .unwrap();
+        assert!(solver.board().is_complete());
+    }
+
+    #[test]
+    fn test_backtracking() {
+        // フィルタだけでは解けないので、仮置きと後戻りを繰り返して解く
+        let lines = [
+            "800000000",
+            "003600000",
+            "070090200",
+            "050007000",
+            "000045700",
+            "000100030",
+            "001000068",
+            "008500010",
+            "090000400",
+        ];
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let mut solver = Solver::new(board);
+        let mut deduced = 0;
+        loop {
+            match solver.update() {
+                StepOutcome::Deduced { .. } => deduced += 1,
+                outcome if !outcome.is_progress() => break,
+                _ => {}
+            }
+        }
+        assert!(solver.board().is_complete());
+
+        // 後戻りで捨てた仮置きの中で使ったフィルタは数えない
+        let counted = solver
+            .statistics()
+            .filters
+            .values()
+            .map(|usage| usage.count)
+            .sum::<usize>();
+        assert!(counted > 0);
+        assert!(counted < deduced);
+
+        let solution =
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452";
+        let digits = solver
+            .board()
+            .items()
+            .map(|sq| sq.digit().unwrap().get().to_string())
+            .join("");
+        assert_eq!(solution, digits);
+
+        let statistics = solver.statistics();
+        assert!(statistics.get_stuck > 1);
+        assert!(statistics.back_tracked > 0);
+
+        // 矛盾する盤面は候補を試し尽くしたところで止まる
+        let mut lines = lines;
+        lines[0] = "800000001";
+        lines[1] = "003600020";
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let mut solver = Solver::new(board);
+        assert_eq!(StepOutcome::Unsolvable, solver.solve().outcome);
+        assert!(!solver.board().is_complete());
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        filters: usize,
+        changed: usize,
+        events: usize,
+        fixes: usize,
+        branches: usize,
+        backtracks: usize,
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Counts>>);
+
+    impl SolverObserver for Recorder {
+        fn on_filter_start(&mut self, _filter: &'static str) {
+            self.0.lock().unwrap().filters += 1;
+        }
+
+        fn on_filter_end(&mut self, _filter: &'static str, changed: bool) {
+            if changed {
+                self.0.lock().unwrap().changed += 1;
+            }
+        }
+
+        fn on_event(&mut self, _event: &Event, changed: bool) {
+            if changed {
+                self.0.lock().unwrap().events += 1;
+            }
+        }
+
+        fn on_fix(&mut self, _pos: ItemPosition, _digit: Digit) {
+            self.0.lock().unwrap().fixes += 1;
+        }
+
+        fn on_branch(&mut self, _pos: ItemPosition, digits: DigitSet) {
+            assert!(digits.len() >= 2);
+            self.0.lock().unwrap().branches += 1;
+        }
+
+        fn on_backtrack(&mut self) {
+            self.0.lock().unwrap().backtracks += 1;
         }
     }
+
+    #[test]
+    fn test_observer() {
+        // フィルタだけで解ける盤面では、空きマスの数だけ確定する
+        let board = load_board(1);
+        let blanks = board.items().filter(|sq| !sq.is_fixed()).count();
+        let recorder = Recorder::default();
+        let mut solver = Solver::new(board);
+        solver.add_observer(recorder.clone());
+        solver.solve();
+
+        let counts = recorder.0.lock().unwrap();
+        assert_eq!(blanks, counts.fixes);
+        assert_eq!(solver.steps().len(), counts.events);
+        assert_eq!(
+            solver
+                .statistics()
+                .filters
+                .values()
+                .map(|u| u.count)
+                .sum::<usize>(),
+            counts.changed
+        );
+        assert!(counts.filters >= counts.changed);
+        assert_eq!(0, counts.branches);
+
+        // 仮置きと後戻りも通知される
+        let lines = [
+            "800000000",
+            "003600000",
+            "070090200",
+            "050007000",
+            "000045700",
+            "000100030",
+            "001000068",
+            "008500010",
+            "090000400",
+        ];
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let recorder = Recorder::default();
+        let mut solver = Solver::new(board);
+        solver.add_observer(recorder.clone());
+        solver.solve();
+
+        let counts = recorder.0.lock().unwrap();
+        let statistics = solver.statistics();
+        assert_eq!(statistics.get_stuck, counts.branches);
+        assert_eq!(statistics.back_tracked, counts.backtracks);
+    }
+
+    #[test]
+    fn test_step_outcome() {
+        // フィルタだけで解ける盤面、確定したセルは盤面に反映されている
+        let mut solver = Solver::new(load_board(1));
+        match solver.update() {
+            StepOutcome::Deduced { filter, placed } => {
+                assert_eq!("NakedSingle", filter);
+                assert!(!placed.is_empty());
+                for (pos, digit) in placed {
+                    assert_eq!(Some(digit), solver.board().item_at(pos).digit());
+                }
+            }
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+
+        let report = solver.solve();
+        assert!(report.is_solved());
+        assert!(report.updates > 0);
+        assert_eq!(StepOutcome::Solved, solver.update());
+
+        // 仮置きしなければ Stuck、仮置きすれば Guessed と Backtracked を経て解ける
+        let lines = [
+            "800000000",
+            "003600000",
+            "070090200",
+            "050007000",
+            "000045700",
+            "000100030",
+            "001000068",
+            "008500010",
+            "090000400",
+        ];
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let report = SolverBuilder::new()
+            .backtracking(false)
+            .build(board.clone())
+            .solve();
+        assert_eq!(StepOutcome::Stuck, report.outcome);
+
+        let mut solver = Solver::new(board);
+        let mut outcomes = Vec::new();
+        loop {
+            let outcome = solver.update();
+            if !outcome.is_progress() {
+                assert_eq!(StepOutcome::Solved, outcome);
+                break;
+            }
+            outcomes.push(outcome);
+        }
+        assert!(outcomes.iter().any(|o| match o {
+            StepOutcome::Guessed { pos, placed } => placed.iter().any(|(p, _)| p == pos),
+            _ => false,
+        }));
+        assert!(outcomes
+            .iter()
+            .any(|o| matches!(o, StepOutcome::Backtracked { .. })));
+
+        // 矛盾した盤面は仮置きしなくても解なしと分かる
+        let mut lines = lines;
+        lines[0] = "800000008";
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let report = SolverBuilder::new()
+            .backtracking(false)
+            .build(board)
+            .solve();
+        assert_eq!(StepOutcome::Unsolvable, report.outcome);
+
+        // 重複はなくても候補のなくなったセル (r1c9) があれば解なし
+        let mut lines = ["000000000"; 9];
+        lines[0] = "123456780";
+        lines[1] = "000000009";
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let report = SolverBuilder::new()
+            .backtracking(false)
+            .build(board)
+            .solve();
+        assert_eq!(StepOutcome::Unsolvable, report.outcome);
+    }
+
+    #[test]
+    fn test_builder() {
+        let builder = SolverBuilder::new().disable("NakedSingle").only(&[
+            "XWing",
+            "NakedSingle",
+            "SingleCandidate",
+        ]);
+        assert_eq!(vec!["XWing", "SingleCandidate"], builder.filter_names());
+        assert_eq!(25, SolverBuilder::new().filter_names().len());
+
+        // 既定のフィルタは易しい順に並ぶ
+        let filters = SolverBuilder::new().filters;
+        assert!(filters
+            .windows(2)
+            .all(|w| w[0].difficulty() <= w[1].difficulty()));
+
+        // 仮置きなしではフィルタで解けるところまでで止まる
+        let lines = [
+            "800000000",
+            "003600000",
+            "070090200",
+            "050007000",
+            "000045700",
+            "000100030",
+            "001000068",
+            "008500010",
+            "090000400",
+        ];
+        let board = BoardLoader::from_lines(lines.iter().copied()).unwrap();
+        let mut solver = SolverBuilder::new().backtracking(false).build(board);
+        solver.solve();
+        assert!(!solver.board().is_complete());
+        assert_eq!(0, solver.statistics().get_stuck);
+
+        // 外部のフィルタだけで、各行の空きが1つの盤面を解く
+        let solution =
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452";
+        let lines = (0..9)
+            .map(|row| {
+                let mut line = solution[row * 9..(row + 1) * 9].to_string();
+                line.replace_range(row..=row, "0");
+                line
+            })
+            .collect_vec();
+        let board = BoardLoader::from_lines(lines.iter().map(|s| s.as_str())).unwrap();
+        let mut solver = SolverBuilder::empty()
+            .filter(FullHouse)
+            .backtracking(false)
+            .build(board);
+        solver.solve();
+        assert!(solver.board().is_complete());
+        assert_eq!(
+            vec!["FullHouse"],
+            solver.statistics().filters.keys().copied().collect_vec()
+        );
+
+        // 難易度を指定しなければ既定値になる
+        assert_eq!(DEFAULT_DIFFICULTY, solver.statistics().difficulty());
+    }
 }
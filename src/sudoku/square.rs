@@ -1,8 +1,10 @@
 use std::fmt;
 
+use crate::sudoku::board::ItemPosition;
 use crate::sudoku::digit::Digit;
 
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Square {
     digit: Option<Digit>,
     row: usize,
@@ -18,6 +20,13 @@ impl Square {
         self.digit
     }
 
+    pub fn position(&self) -> ItemPosition {
+        ItemPosition {
+            row: self.row,
+            col: self.column,
+        }
+    }
+
     pub fn is_fixed(&self) -> bool {
         self.digit.is_some()
     }
@@ -27,6 +36,10 @@ impl Square {
         self.digit = Some(digit);
     }
 
+    pub fn clear(&mut self) {
+        self.digit = None;
+    }
+
     pub fn row(&self) -> usize {
         self.row
     }
@@ -38,10 +51,7 @@ impl Square {
 
 impl fmt::Display for Square {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let c = self
-            .digit
-            .and_then(|d| std::char::from_digit(d.get() as u32, 10))
-            .unwrap_or('-');
+        let c = self.digit.map(char::from).unwrap_or('-');
         fmt::Display::fmt(&c, f)
     }
 }
@@ -0,0 +1,235 @@
+use std::fmt::{self, Debug};
+
+use std::iter::FromIterator;
+
+use itertools::Itertools;
+
+use crate::sudoku::board::{ItemPosition, Scope, UnitKind};
+use crate::sudoku::candidate::Candidate;
+use crate::sudoku::digit::Digit;
+use crate::sudoku::event::Event;
+use crate::sudoku::filter::NamedFilter;
+use crate::sudoku::set::DigitSet;
+use crate::sudoku::Board;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StepKind {
+    Retain,
+    Remove,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum House {
+    Row(usize),
+    Column(usize),
+    Block(usize),
+    Unit(UnitKind),
+    Cage(usize),
+}
+
+impl House {
+    pub(crate) fn new<T: Debug + Clone>(scope: Scope, board: &Board<T>) -> Self {
+        match scope {
+            Scope::Row(row) => House::Row(row),
+            Scope::Column(col) => House::Column(col),
+            Scope::BoardBlock(pos) => House::Block(board.block_index(pos)),
+            Scope::Unit(unit) => House::Unit(board.units()[unit].kind()),
+            Scope::Cage(cage) => House::Cage(cage),
+        }
+    }
+}
+
+impl fmt::Display for House {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            House::Row(row) => write!(f, "row {}", row + 1),
+            House::Column(col) => write!(f, "column {}", col + 1),
+            House::Block(block) => write!(f, "block {}", block + 1),
+            House::Unit(UnitKind::Diagonal) => write!(f, "diagonal"),
+            House::Unit(UnitKind::AntiDiagonal) => write!(f, "anti-diagonal"),
+            House::Unit(UnitKind::Window) => write!(f, "window"),
+            House::Unit(UnitKind::Custom) => write!(f, "extra house"),
+            House::Cage(cage) => write!(f, "cage {}", cage + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub filter: &'static str,
+    pub kind: StepKind,
+    pub house: Option<House>,
+    pub digits: DigitSet,
+    pub cells: Vec<ItemPosition>,
+    pub removed: Vec<(ItemPosition, DigitSet)>,
+    pub pattern: Vec<ItemPosition>,
+    pub pattern_digits: DigitSet,
+}
+
+impl Step {
+    pub(crate) fn new(
+        event: &Event,
+        cells: Vec<ItemPosition>,
+        before: Vec<DigitSet>,
+        candidates: &Board<Candidate>,
+    ) -> Self {
+        // 評価前後の候補を比較して、実際に除外された数字を記録する
+        let removed = cells
+            .iter()
+            .zip(before)
+            .map(|(pos, digits)| {
+                let after = candidates.item_at(*pos).digits();
+                (
+                    *pos,
+                    DigitSet::from_iter(digits.iter().filter(|d| !after.contains(*d))),
+                )
+            })
+            .filter(|(_, removed)| !removed.is_empty())
+            .collect();
+
+        let house = event
+            .scope()
+            .house()
+            .map(|scope| House::new(scope, candidates));
+
+        Step {
+            filter: event.filter(),
+            kind: event.kind(),
+            house,
+            digits: event.digits(),
+            cells,
+            removed,
+            pattern: event.pattern().to_vec(),
+            pattern_digits: event.pattern_digits(),
+        }
+    }
+
+    pub fn affected_cells(&self) -> impl Iterator<Item = ItemPosition> + '_ {
+        self.removed.iter().map(|(pos, _)| *pos)
+    }
+
+    pub fn removed_digits(&self) -> DigitSet {
+        self.removed
+            .iter()
+            .fold(DigitSet::default(), |digits, (_, ds)| digits | *ds)
+    }
+}
+
+fn format_digits(digits: DigitSet) -> String {
+    digits.iter().map(|d| d.to_string()).join(",")
+}
+
+fn format_cells(cells: impl Iterator<Item = ItemPosition>) -> String {
+    cells
+        .map(|pos| format!("r{}c{}", pos.row + 1, pos.col + 1))
+        .join(",")
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let house = self
+            .house
+            .map(|house| format!(" in {}", house))
+            .unwrap_or_default();
+
+        // 例: "HiddenPair {3,7} in row 4 at r4c2,r4c8 removes 1,5,9"
+        //     "NakedPair {3,7} at r4c2,r4c8 removes 3 in row 4 from r4c5"
+        //     "CageCombination {1,2,4} at r1c1,r1c2,r1c3 keeps {1,2} at r1c1 removes 4"
+        match self.kind {
+            StepKind::Retain if !self.pattern.is_empty() && self.pattern != self.cells => write!(
+                f,
+                "{} {{{}}} at {} keeps {{{}}}{} at {} removes {}",
+                self.filter,
+                format_digits(self.pattern_digits),
+                format_cells(self.pattern.iter().copied()),
+                format_digits(self.digits),
+                house,
+                format_cells(self.cells.iter().copied()),
+                format_digits(self.removed_digits())
+            ),
+            StepKind::Retain => write!(
+                f,
+                "{} {{{}}}{} at {} removes {}",
+                self.filter,
+                format_digits(self.digits),
+                house,
+                format_cells(self.cells.iter().copied()),
+                format_digits(self.removed_digits())
+            ),
+            StepKind::Remove if !self.pattern.is_empty() => write!(
+                f,
+                "{} {{{}}} at {} removes {}{} from {}",
+                self.filter,
+                format_digits(self.pattern_digits),
+                format_cells(self.pattern.iter().copied()),
+                format_digits(self.digits),
+                house,
+                format_cells(self.affected_cells())
+            ),
+            StepKind::Remove => write!(
+                f,
+                "{} removes {}{} from {}",
+                self.filter,
+                format_digits(self.digits),
+                house,
+                format_cells(self.affected_cells())
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub filter: &'static str,
+    pub difficulty: f32,
+    pub steps: Vec<Step>,
+    pub placements: Vec<(ItemPosition, Digit)>,
+}
+
+impl Hint {
+    pub(crate) fn new(
+        filter: &dyn NamedFilter,
+        steps: Vec<Step>,
+        candidates: &Board<Candidate>,
+    ) -> Self {
+        // 候補が1つに絞られたセルは配置できる
+        let placements = steps
+            .iter()
+            .flat_map(|step| step.affected_cells())
+            .unique_by(|pos| (pos.row, pos.col))
+            .filter_map(|pos| {
+                let c = candidates.item_at(pos);
+                if c.is_fixed() {
+                    c.possible_digits().next().map(|d| (pos, d))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Hint {
+            filter: filter.name(),
+            difficulty: filter.difficulty(),
+            steps,
+            placements,
+        }
+    }
+
+    pub fn cells(&self) -> Vec<ItemPosition> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.cells.iter().copied())
+            .unique_by(|pos| (pos.row, pos.col))
+            .collect()
+    }
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.steps.iter().join("; "))?;
+        for (pos, digit) in self.placements.iter() {
+            write!(f, "; r{}c{} = {}", pos.row + 1, pos.col + 1, digit)?;
+        }
+        Ok(())
+    }
+}